@@ -0,0 +1,251 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use resvg::usvg::Size;
+
+use crate::document::Document;
+use crate::error::Error;
+use crate::export::{export_png, generate_png, generate_svg_tree};
+use crate::layers::{set_visible_layers, Layer};
+use crate::presets::Preset;
+
+/// Output names of batch exports, `{stem}` being the name of the document
+/// without extension, `{layer}` the name of the layer or preset and `{index}`
+/// the position of the image in the batch, from 1.
+pub(crate) const DEFAULT_TEMPLATE: &str = "{stem}-{layer}.png";
+
+/// Layer combinations exported one PNG each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Batch {
+    // a single image, with the layers as they are
+    #[default]
+    Current,
+    // every top-level layer on its own
+    EachLayer,
+    EachPreset,
+    // the first top-level layer, then the first two, and so on, as for slides
+    Cumulative,
+}
+
+impl Batch {
+    pub(crate) const ALL: [Batch; 4] = [
+        Batch::Current,
+        Batch::EachLayer,
+        Batch::EachPreset,
+        Batch::Cumulative,
+    ];
+
+    pub(crate) fn parse(batch: &str) -> Option<Self> {
+        match batch {
+            "layer" => Some(Batch::EachLayer),
+            "preset" => Some(Batch::EachPreset),
+            "cumulative" => Some(Batch::Cumulative),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Batch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Batch::Current => write!(f, "Current layers"),
+            Batch::EachLayer => write!(f, "Each layer alone"),
+            Batch::EachPreset => write!(f, "Each preset"),
+            Batch::Cumulative => write!(f, "Cumulative builds"),
+        }
+    }
+}
+
+/// The layer states of every image of the batch, with their names. Sublayers
+/// keep their own state, shown or hidden along with their top-level layer.
+fn batch_layers(batch: Batch, layers: &[Layer], presets: &[Preset]) -> Vec<(String, Vec<Layer>)> {
+    let top_level: Vec<usize> = (0..layers.len())
+        .filter(|i| layers[*i].parent.is_none())
+        .collect();
+    let showing = |shown: &[usize]| {
+        let mut layers = layers.to_vec();
+        for index in &top_level {
+            layers[*index].visible = shown.contains(index);
+        }
+        layers
+    };
+    match batch {
+        Batch::Current => vec![(String::new(), layers.to_vec())],
+        Batch::EachLayer => top_level
+            .iter()
+            .map(|index| (layers[*index].name.clone(), showing(&[*index])))
+            .collect(),
+        Batch::EachPreset => presets
+            .iter()
+            .map(|preset| {
+                let mut layers = layers.to_vec();
+                preset.apply(&mut layers);
+                (preset.name.clone(), layers)
+            })
+            .collect(),
+        Batch::Cumulative => (0..top_level.len())
+            .map(|count| {
+                let last = &layers[top_level[count]];
+                (last.name.clone(), showing(&top_level[..=count]))
+            })
+            .collect(),
+    }
+}
+
+/// Fills the template, relative paths being relative to the given directory,
+/// or to the current one.
+fn output_name(
+    template: &str,
+    dir: Option<&Path>,
+    file_name: &str,
+    name: &str,
+    index: usize,
+) -> String {
+    let path = Path::new(file_name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    // a layer name is not a path
+    let name = name.replace(['/', '\\'], "_");
+    let output = template
+        .replace("{stem}", &stem)
+        .replace("{layer}", &name)
+        .replace("{index}", &index.to_string());
+    let output = PathBuf::from(output);
+    match dir {
+        Some(dir) if output.is_relative() => dir.join(output),
+        _ => output,
+    }
+    .to_string_lossy()
+    .to_string()
+}
+
+/// Writes one PNG per image of the batch, scaled, under names from the template,
+/// relative to `dir` when given, returning their paths. Does
+/// nothing, returning `None`, when one of them exists and cannot be overwritten,
+/// and fails when there is nothing to export or two images would have the same
+/// path, as layers with the same name do.
+pub(crate) fn export_batch(
+    document: &Document,
+    batch: Batch,
+    template: &str,
+    dir: Option<&Path>,
+    scale: f32,
+    overwrite: bool,
+) -> Result<Option<Vec<String>>, Error> {
+    let outputs: Vec<(String, Vec<Layer>)> =
+        batch_layers(batch, &document.layers, &document.presets)
+            .into_iter()
+            .enumerate()
+            .map(|(index, (name, layers))| {
+                let mut output = PathBuf::from(output_name(
+                    template,
+                    dir,
+                    &document.file_name,
+                    &name,
+                    index + 1,
+                ));
+                output.set_extension("png");
+                (output.to_string_lossy().to_string(), layers)
+            })
+            .collect();
+    if outputs.is_empty() {
+        let what = match batch {
+            Batch::EachPreset => "presets",
+            _ => "layers",
+        };
+        return Err(Error::NothingToExport(what));
+    }
+    for (index, (output, _)) in outputs.iter().enumerate() {
+        if outputs[..index]
+            .iter()
+            .any(|(previous, _)| previous == output)
+        {
+            return Err(Error::DuplicateOutput(output.to_string()));
+        }
+    }
+    if !overwrite && outputs.iter().any(|(output, _)| Path::new(output).exists()) {
+        return Ok(None);
+    }
+
+    for (output, layers) in &outputs {
+        let svg_content = set_visible_layers(&document.svg_content, layers)?;
+        let (svg_tree, pixmap_size) = generate_svg_tree(&svg_content)?;
+        let output_size = Size::from_wh(pixmap_size.width() * scale, pixmap_size.height() * scale)
+            .ok_or_else(|| Error::Render("invalid output size".to_string()))?;
+        let png_content = generate_png(&svg_tree, &output_size, scale)?;
+        export_png(&png_content, output, true)?;
+    }
+    Ok(Some(
+        outputs.into_iter().map(|(output, _)| output).collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::{get_layers, LayerKey};
+
+    const SVG: &[u8] = br#"<svg xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape">
+  <g inkscape:groupmode="layer" id="back" inkscape:label="Back">
+    <g inkscape:groupmode="layer" id="grid" inkscape:label="Grid"/>
+  </g>
+  <g inkscape:groupmode="layer" id="text" inkscape:label="Text" style="display:none"/>
+  <g inkscape:groupmode="layer" id="notes" inkscape:label="Notes"/>
+</svg>"#;
+
+    fn visible(layers: &[Layer]) -> Vec<bool> {
+        layers.iter().map(|l| l.visible).collect()
+    }
+
+    #[test]
+    fn fills_the_template() {
+        let name = output_name("{stem}-{index}-{layer}.png", None, "/d/a.svg", "Back", 2);
+        assert_eq!(name, "a-2-Back.png");
+        // a layer name does not make directories
+        let name = output_name("{layer}.png", None, "/d/a.svg", "a/b", 1);
+        assert_eq!(name, "a_b.png");
+    }
+
+    #[test]
+    fn resolves_relative_names_against_the_directory() {
+        let dir = Some(Path::new("/d"));
+        let name = output_name("out/{layer}.png", dir, "/d/a.svg", "Back", 1);
+        assert_eq!(Path::new(&name), Path::new("/d/out/Back.png"));
+        let name = output_name("/tmp/{layer}.png", dir, "/d/a.svg", "Back", 1);
+        assert_eq!(name, "/tmp/Back.png");
+    }
+
+    #[test]
+    fn shows_each_top_level_layer_alone() {
+        let layers = get_layers(SVG).unwrap();
+        let batch = batch_layers(Batch::EachLayer, &layers, &[]);
+        let names: Vec<&str> = batch.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Back", "Text", "Notes"]);
+        // sublayers keep their state
+        assert_eq!(visible(&batch[0].1), vec![true, true, false, false]);
+        assert_eq!(visible(&batch[1].1), vec![false, true, true, false]);
+    }
+
+    #[test]
+    fn builds_layers_up() {
+        let layers = get_layers(SVG).unwrap();
+        let batch = batch_layers(Batch::Cumulative, &layers, &[]);
+        assert_eq!(batch.len(), 3);
+        assert_eq!(visible(&batch[0].1), vec![true, true, false, false]);
+        assert_eq!(visible(&batch[1].1), vec![true, true, true, false]);
+        assert_eq!(visible(&batch[2].1), vec![true, true, true, true]);
+    }
+
+    #[test]
+    fn applies_each_preset() {
+        let layers = get_layers(SVG).unwrap();
+        let preset = Preset {
+            name: "print".to_string(),
+            layers: vec![(LayerKey::Id("notes".to_string()), false)],
+        };
+        let batch = batch_layers(Batch::EachPreset, &layers, &[preset]);
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].0, "print");
+        assert_eq!(visible(&batch[0].1), vec![true, true, false, false]);
+        assert!(batch_layers(Batch::EachPreset, &layers, &[]).is_empty());
+    }
+}
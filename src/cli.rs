@@ -0,0 +1,304 @@
+use crate::batch::{export_batch, Batch, DEFAULT_TEMPLATE};
+use crate::diff::diff_trees;
+use crate::document::{load_svg, Document};
+use crate::error::Error;
+use crate::export::{encode_png, export_png, generate_png, generate_svg_tree};
+use crate::layers::{set_visible_layers, LayerKey};
+use crate::presets::load_presets;
+use resvg::usvg::Size;
+use std::path::Path;
+
+pub(crate) const EXPORT_USAGE: &str = "\
+Usage: viewrs export <file.svg> [options]
+
+Options:
+  -o, --output <file.png>  output path (defaults to the input with a .png extension), or
+                           output name template with --each (defaults to {stem}-{layer}.png)
+  --each <batch>           one PNG per combination of layers: each `layer` alone, each
+                           `preset`, or `cumulative` builds (layer 1, then 1+2, ...)
+  --width <pixels>         output width, keeping the aspect ratio
+  --height <pixels>        output height, keeping the aspect ratio, instead of --width
+  --preset <name>          show and hide layers as the preset says, before --hide/--show
+  --hide <layer>           hide a layer, by label or id (repeatable)
+  --show <layer>           show a layer, by label or id (repeatable)";
+
+pub(crate) const DIFF_USAGE: &str = "\
+Usage: viewrs diff <before.svg> <after.svg> [options]
+
+Exits with 0 when the renderings match, 1 when they differ beyond the threshold,
+2 on errors.
+
+Options:
+  -o, --output <file.png>  write the difference overlay, changed pixels in red
+  --width <pixels>         rendering width (defaults to the width of before.svg)
+  --height <pixels>        rendering height (defaults to the height of before.svg)
+  --threshold <percent>    changed pixels tolerated, in percent (defaults to 0)";
+
+#[derive(Debug, Default)]
+pub(crate) struct ExportArgs {
+    input: String,
+    output: Option<String>,
+    width: Option<f32>,
+    height: Option<f32>,
+    preset: Option<String>,
+    each: Option<Batch>,
+    // layer visibility changes, applied in command line order
+    layers: Vec<(String, bool)>,
+}
+
+pub(crate) fn parse_export_args(args: &[String]) -> Result<ExportArgs, String> {
+    let mut export_args = ExportArgs::default();
+    let mut input = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "-o" | "--output" => export_args.output = Some(parse_png(arg, &value()?)?),
+            "--width" => export_args.width = Some(parse_size(arg, &value()?)?),
+            "--height" => export_args.height = Some(parse_size(arg, &value()?)?),
+            "--preset" => export_args.preset = Some(value()?),
+            "--each" => {
+                let value = value()?;
+                let batch = Batch::parse(&value);
+                export_args.each =
+                    Some(batch.ok_or_else(|| format!("invalid value for {arg}: {value}"))?);
+            }
+            "--hide" => export_args.layers.push((value()?, false)),
+            "--show" => export_args.layers.push((value()?, true)),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ if input.is_none() => input = Some(arg.to_string()),
+            _ => return Err(format!("unexpected argument {arg}")),
+        }
+    }
+    if export_args.width.is_some() && export_args.height.is_some() {
+        return Err("--width and --height cannot be used together".to_string());
+    }
+    export_args.input = input.ok_or("missing input file")?;
+    Ok(export_args)
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct DiffArgs {
+    before: String,
+    after: String,
+    output: Option<String>,
+    width: Option<f32>,
+    height: Option<f32>,
+    threshold: f32,
+}
+
+pub(crate) fn parse_diff_args(args: &[String]) -> Result<DiffArgs, String> {
+    let mut diff_args = DiffArgs::default();
+    let mut inputs = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "-o" | "--output" => diff_args.output = Some(parse_png(arg, &value()?)?),
+            "--width" => diff_args.width = Some(parse_size(arg, &value()?)?),
+            "--height" => diff_args.height = Some(parse_size(arg, &value()?)?),
+            "--threshold" => {
+                let value = value()?;
+                diff_args.threshold = match value.parse::<f32>() {
+                    Ok(threshold) if (0.0..=100.0).contains(&threshold) => threshold,
+                    _ => return Err(format!("invalid value for {arg}: {value}")),
+                };
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ if inputs.len() < 2 => inputs.push(arg.to_string()),
+            _ => return Err(format!("unexpected argument {arg}")),
+        }
+    }
+    let mut inputs = inputs.into_iter();
+    diff_args.before = inputs.next().ok_or("missing input files")?;
+    diff_args.after = inputs.next().ok_or("missing second input file")?;
+    Ok(diff_args)
+}
+
+/// Compares the renderings of both files, returning whether they match within
+/// the threshold.
+pub(crate) fn diff(args: DiffArgs) -> Result<bool, Error> {
+    let (_, _, before, _) = load_svg(&args.before)?;
+    let (_, _, after, _) = load_svg(&args.after)?;
+    let (before, size) = generate_svg_tree(&before)?;
+    let (after, _) = generate_svg_tree(&after)?;
+
+    let ratio = size.width() / size.height();
+    let (width, height) = match (args.width, args.height) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(width), None) => (width, width / ratio),
+        (None, Some(height)) => (height * ratio, height),
+        (None, None) => (size.width(), size.height()),
+    };
+    let (width, height) = (width.round() as u32, height.round() as u32);
+    let diff = diff_trees(&before, &after, width, height)?;
+    println!(
+        "{} of {} pixels differ ({:.2}%)",
+        diff.changed,
+        diff.total,
+        diff.percentage()
+    );
+
+    let matching = diff.percentage() <= args.threshold;
+    if let Some(output) = args.output {
+        let png_content = encode_png(diff.pixels, width, height)?;
+        export_png(&png_content, &output, true)?;
+    }
+    Ok(matching)
+}
+
+pub(crate) fn export(args: ExportArgs) -> Result<(), Error> {
+    let (file_name, source, svg_content, mut layers) = load_svg(&args.input)?;
+    let presets = load_presets(&file_name)?;
+    if let Some(name) = &args.preset {
+        let preset = presets.iter().find(|p| p.name == *name);
+        preset
+            .ok_or_else(|| Error::UnknownPreset(name.to_string()))?
+            .apply(&mut layers);
+    }
+    for (name, visible) in &args.layers {
+        let key = LayerKey::Id(name.to_string());
+        let mut matching = layers
+            .iter_mut()
+            .filter(|l| l.name == *name || l.key == key)
+            .peekable();
+        if matching.peek().is_none() {
+            return Err(Error::UnknownLayer(name.to_string()));
+        }
+        matching.for_each(|l| l.visible = *visible);
+    }
+    let svg_content = set_visible_layers(&svg_content, &layers)?;
+
+    let (svg_tree, pixmap_size) = generate_svg_tree(&svg_content)?;
+    let scale = match (args.width, args.height) {
+        (Some(width), _) => width / pixmap_size.width(),
+        (None, Some(height)) => height / pixmap_size.height(),
+        (None, None) => 1.0,
+    };
+    if let Some(batch) = args.each {
+        let mut document = Document::new(file_name, source, svg_content, layers)?;
+        document.presets = presets;
+        // next to the input by default, relative to the current directory
+        // otherwise, as single exports
+        let dir = Path::new(&document.file_name).parent();
+        let outputs = match &args.output {
+            Some(template) => export_batch(&document, batch, template, None, scale, true)?,
+            None => export_batch(&document, batch, DEFAULT_TEMPLATE, dir, scale, true)?,
+        };
+        for output in outputs.unwrap_or_default() {
+            println!("{output}");
+        }
+        return Ok(());
+    }
+    let output_size = Size::from_wh(pixmap_size.width() * scale, pixmap_size.height() * scale)
+        .ok_or_else(|| Error::Render("invalid output size".to_string()))?;
+    let png_content = generate_png(&svg_tree, &output_size, scale)?;
+
+    let output = args.output.unwrap_or_else(|| args.input.clone());
+    export_png(&png_content, &output, true)?;
+    Ok(())
+}
+
+// written as given, rather than with its extension replaced
+fn parse_png(option: &str, value: &str) -> Result<String, String> {
+    let extension = Path::new(value).extension().unwrap_or_default();
+    if extension.eq_ignore_ascii_case("png") {
+        Ok(value.to_string())
+    } else {
+        Err(format!(
+            "invalid value for {option}: {value}, not a .png file"
+        ))
+    }
+}
+
+fn parse_size(option: &str, value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(size) if size > 0.0 => Ok(size),
+        _ => Err(format!("invalid value for {option}: {value}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_export_options() {
+        let export_args = parse_export_args(&args(&[
+            "a.svg", "--width", "640", "--hide", "Guides", "--show", "l2", "--each", "layer",
+        ]))
+        .unwrap();
+        assert_eq!(export_args.input, "a.svg");
+        assert_eq!(export_args.width, Some(640.0));
+        assert_eq!(export_args.each, Some(Batch::EachLayer));
+        assert_eq!(
+            export_args.layers,
+            vec![("Guides".to_string(), false), ("l2".to_string(), true)]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_export_options() {
+        assert!(parse_export_args(&args(&[])).is_err());
+        assert!(parse_export_args(&args(&["a.svg", "b.svg"])).is_err());
+        assert!(parse_export_args(&args(&["a.svg", "--width"])).is_err());
+        assert!(parse_export_args(&args(&["a.svg", "--width", "-3"])).is_err());
+        assert!(parse_export_args(&args(&["a.svg", "--width", "1", "--height", "1"])).is_err());
+        assert!(parse_export_args(&args(&["a.svg", "--each", "page"])).is_err());
+        assert!(parse_export_args(&args(&["a.svg", "--dpi", "300"])).is_err());
+    }
+
+    #[test]
+    fn writes_png_files_only() {
+        let output = |o| parse_export_args(&args(&["a.svg", "-o", o])).map(|a| a.output);
+        assert_eq!(output("out.png"), Ok(Some("out.png".to_string())));
+        assert_eq!(output("OUT.PNG"), Ok(Some("OUT.PNG".to_string())));
+        assert!(output("out.jpg").is_err());
+        assert!(output("out").is_err());
+    }
+
+    #[test]
+    fn parses_diff_options() {
+        let diff_args = parse_diff_args(&args(&[
+            "a.svg",
+            "b.svg",
+            "--threshold",
+            "0.5",
+            "-o",
+            "d.png",
+        ]))
+        .unwrap();
+        assert_eq!(
+            (diff_args.before.as_str(), diff_args.after.as_str()),
+            ("a.svg", "b.svg")
+        );
+        assert_eq!(diff_args.threshold, 0.5);
+        assert_eq!(diff_args.output.as_deref(), Some("d.png"));
+        assert_eq!(
+            parse_diff_args(&args(&["a.svg", "b.svg"]))
+                .unwrap()
+                .threshold,
+            0.0
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_diff_options() {
+        assert!(parse_diff_args(&args(&["a.svg"])).is_err());
+        assert!(parse_diff_args(&args(&["a.svg", "b.svg", "c.svg"])).is_err());
+        assert!(parse_diff_args(&args(&["a.svg", "b.svg", "--threshold", "101"])).is_err());
+        assert!(parse_diff_args(&args(&["a.svg", "b.svg", "--threshold", "x"])).is_err());
+    }
+}
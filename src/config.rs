@@ -0,0 +1,34 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+
+/// The directory holding the configuration of the user, following the XDG
+/// base directory specification.
+pub(crate) fn config_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("viewrs"))
+}
+
+/// Reads a file of the configuration directory, `None` when the user did not
+/// write one.
+pub(crate) fn read_config(name: &str) -> Result<Option<(PathBuf, String)>, Error> {
+    let Some(path) = config_dir().map(|dir| dir.join(name)) else {
+        return Ok(None);
+    };
+    Ok(read_optional(&path)?.map(|content| (path, content)))
+}
+
+/// Reads a text file, `None` when it does not exist.
+pub(crate) fn read_optional(path: &Path) -> Result<Option<String>, Error> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Error::io(&path.to_string_lossy(), e)),
+    }
+}
@@ -0,0 +1,79 @@
+use resvg::tiny_skia::Transform;
+use resvg::usvg::Tree;
+
+use crate::error::Error;
+use crate::export::generate_pixels;
+
+// largest difference of a channel still considered unchanged, absorbing
+// antialiasing noise
+const TOLERANCE: u8 = 16;
+const HIGHLIGHT: [u8; 4] = [230, 0, 0, 255];
+
+/// Pixel difference between two renderings, as an overlay highlighting the
+/// changed pixels over a faded copy of the second rendering.
+pub(crate) struct Diff {
+    // opaque RGBA pixels
+    pub(crate) pixels: Vec<u8>,
+    pub(crate) changed: usize,
+    pub(crate) total: usize,
+}
+
+impl Diff {
+    pub(crate) fn percentage(&self) -> f32 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.changed as f32 * 100.0 / self.total as f32
+        }
+    }
+}
+
+/// Compares two straight RGBA renderings of the same size.
+pub(crate) fn diff_pixels(before: &[u8], after: &[u8]) -> Diff {
+    let mut pixels = Vec::with_capacity(after.len());
+    let mut changed = 0;
+    for (b, a) in before.chunks_exact(4).zip(after.chunks_exact(4)) {
+        let same = b.iter().zip(a).all(|(b, a)| b.abs_diff(*a) <= TOLERANCE)
+            // fully transparent pixels are the same whatever their color
+            || (b[3] == 0 && a[3] == 0);
+        if same {
+            let faded = 255 - (255 - luminance(a)) / 4;
+            pixels.extend([faded, faded, faded, 255]);
+        } else {
+            changed += 1;
+            pixels.extend(HIGHLIGHT);
+        }
+    }
+    Diff {
+        pixels,
+        changed,
+        total: before.len().min(after.len()) / 4,
+    }
+}
+
+/// Renders both trees at the same size, stretching them if their aspect ratios
+/// differ, and compares them.
+pub(crate) fn diff_trees(
+    before: &Tree,
+    after: &Tree,
+    width: u32,
+    height: u32,
+) -> Result<Diff, Error> {
+    let render = |tree: &Tree| {
+        let transform = Transform::from_scale(
+            width as f32 / tree.size().width(),
+            height as f32 / tree.size().height(),
+        );
+        generate_pixels(tree, width, height, transform)
+            .ok_or_else(|| Error::Render(format!("invalid size {width}x{height}")))
+    };
+    Ok(diff_pixels(&render(before)?, &render(after)?))
+}
+
+// luminance of the pixel drawn over white
+fn luminance(pixel: &[u8]) -> u8 {
+    let alpha = pixel[3] as u32;
+    let over_white = |c: u8| (c as u32 * alpha + 255 * (255 - alpha)) / 255;
+    let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(over_white);
+    ((r * 299 + g * 587 + b * 114) / 1000) as u8
+}
@@ -0,0 +1,210 @@
+use std::fs;
+use std::mem;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use resvg::usvg::{Size, Tree};
+
+use crate::error::Error;
+use crate::export::generate_svg_tree;
+use crate::fonts::replace_fonts;
+use crate::layers::{get_layers, set_visible_layers, Layer};
+use crate::presets::Preset;
+use crate::transform::{fit, Matrix, MAX_ZOOM, MIN_ZOOM};
+
+const IDENTITY: Matrix = (1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+
+/// An open document, with its own layer state and zoom/pan.
+#[derive(Debug, Clone)]
+pub(crate) struct Document {
+    pub(crate) file_name: String,
+    pub(crate) modified: Option<SystemTime>,
+    // modification time and size of the file when a reload failed, retried
+    // only once the file changes again
+    pub(crate) reload_failed: Option<(SystemTime, u64)>,
+    // the file as read, what "Save SVG as…" writes with the layer state applied
+    pub(crate) source: Vec<u8>,
+    // the document with fonts replaced and the layer state applied, without
+    // zoom/pan, for rendering only
+    pub(crate) svg_content: Vec<u8>,
+    pub(crate) layers: Vec<Layer>,
+    pub(crate) svg_tree: Arc<Tree>,
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+    pub(crate) matrix_transform: Matrix,
+    // zoom relative to the document fitting the view
+    pub(crate) current_scroll: f32,
+    // where to export, kept per document
+    pub(crate) output_file_name: String,
+    pub(crate) output_svg_file_name: String,
+    // loaded separately, a malformed presets file should not prevent opening
+    pub(crate) presets: Vec<Preset>,
+}
+
+impl Document {
+    pub(crate) fn open(path: &str) -> Result<Self, Error> {
+        let (file_name, source, svg_content, layers) = load_svg(path)?;
+        Document::new(file_name, source, svg_content, layers)
+    }
+
+    pub(crate) fn new(
+        file_name: String,
+        source: Vec<u8>,
+        svg_content: Vec<u8>,
+        layers: Vec<Layer>,
+    ) -> Result<Self, Error> {
+        let (svg_tree, pixmap_size) = generate_svg_tree(&svg_content)?;
+
+        let mut png_path = PathBuf::from(&file_name);
+        png_path.set_extension("png");
+        let mut svg_path = PathBuf::from(&file_name);
+        svg_path.set_file_name(format!(
+            "{}-export.svg",
+            svg_path.file_stem().unwrap_or_default().to_string_lossy()
+        ));
+
+        Ok(Document {
+            modified: fs::metadata(&file_name).and_then(|m| m.modified()).ok(),
+            reload_failed: None,
+            file_name,
+            source,
+            svg_content,
+            layers,
+            svg_tree: Arc::new(svg_tree),
+            width: pixmap_size.width(),
+            height: pixmap_size.height(),
+            matrix_transform: IDENTITY,
+            current_scroll: 1.0,
+            output_file_name: png_path.to_string_lossy().to_string(),
+            output_svg_file_name: svg_path.to_string_lossy().to_string(),
+            presets: Vec::new(),
+        })
+    }
+
+    /// The name shown for the document, without its directory.
+    pub(crate) fn title(&self) -> String {
+        PathBuf::from(&self.file_name)
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    }
+
+    pub(crate) fn ratio(&self) -> f32 {
+        self.width / self.height
+    }
+
+    /// Reloads the document from disk, keeping the state of the layers that are
+    /// still there and the current zoom/pan.
+    pub(crate) fn reload(&mut self) -> Result<(), Error> {
+        let (file_name, source, svg_content, mut layers) = load_svg(&self.file_name)?;
+        for layer in &mut layers {
+            if let Some(old) = self.layers.iter().find(|l| l.key == layer.key) {
+                layer.visible = old.visible;
+                layer.opacity = old.opacity;
+                layer.expanded = old.expanded;
+            }
+        }
+        let svg_content = set_visible_layers(&svg_content, &layers)?;
+
+        let reloaded = Document::new(file_name, source, svg_content, layers)?;
+        *self = Document {
+            matrix_transform: self.matrix_transform,
+            current_scroll: self.current_scroll,
+            output_file_name: mem::take(&mut self.output_file_name),
+            output_svg_file_name: mem::take(&mut self.output_svg_file_name),
+            presets: mem::take(&mut self.presets),
+            ..reloaded
+        };
+        Ok(())
+    }
+
+    /// Re-parses the document after a change to the layers, the only time the
+    /// tree needs rebuilding.
+    pub(crate) fn apply_layers(&mut self) -> Result<(), Error> {
+        let svg_content = set_visible_layers(&self.svg_content, &self.layers)?;
+        let (svg_tree, _) = generate_svg_tree(&svg_content)?;
+        self.svg_content = svg_content;
+        self.svg_tree = Arc::new(svg_tree);
+        Ok(())
+    }
+
+    /// Shows and hides the layers as the preset says.
+    pub(crate) fn apply_preset(&mut self, name: &str) -> Result<(), Error> {
+        let preset = self.presets.iter().find(|p| p.name == name);
+        let Some(preset) = preset else {
+            return Err(Error::UnknownPreset(name.to_string()));
+        };
+        preset.apply(&mut self.layers);
+        self.apply_layers()
+    }
+
+    /// Pixels per document unit when the whole document fits the bounds.
+    pub(crate) fn fit_scale(&self, bounds: (f32, f32)) -> f32 {
+        match Size::from_wh(self.width, self.height) {
+            Some(document_size) if bounds.0 > 0.0 && bounds.1 > 0.0 => fit(document_size, bounds).0,
+            _ => 0.0,
+        }
+    }
+
+    /// Zooms to the given level, relative to the document fitting the view,
+    /// keeping the point in place.
+    pub(crate) fn zoom_around(&mut self, zoom: f32, (x, y): (f32, f32)) {
+        let zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        let scale = zoom / self.current_scroll;
+        self.current_scroll = zoom;
+
+        let (a, b, c, d, e, f) = self.matrix_transform;
+        self.matrix_transform = (
+            a * scale,
+            b * scale,
+            c * scale,
+            d * scale,
+            e * scale + (1.0 - scale) * x,
+            f * scale + (1.0 - scale) * y,
+        );
+    }
+
+    /// Moves the document by the given amount of document units.
+    pub(crate) fn pan(&mut self, x: f32, y: f32) {
+        let (a, b, c, d, e, f) = self.matrix_transform;
+        self.matrix_transform = (a, b, c, d, e + x, f + y);
+    }
+
+    /// Fits the whole document in the view, without pan.
+    pub(crate) fn reset(&mut self) {
+        self.matrix_transform = IDENTITY;
+        self.current_scroll = 1.0;
+    }
+
+    /// Replaces the zoom/pan with one computed from the document size and the
+    /// bounds of the view.
+    pub(crate) fn set_matrix(
+        &mut self,
+        matrix: fn(Size, (f32, f32)) -> Matrix,
+        bounds: (f32, f32),
+    ) {
+        let Some(document_size) = Size::from_wh(self.width, self.height) else {
+            return;
+        };
+        if bounds.0 > 0.0 && bounds.1 > 0.0 {
+            self.matrix_transform = matrix(document_size, bounds);
+            self.current_scroll = self.matrix_transform.0;
+        }
+    }
+}
+
+// canonical path, content as read, content with fonts replaced, and layers
+type LoadedSvg = (String, Vec<u8>, Vec<u8>, Vec<Layer>);
+
+/// Reads an SVG file, returning its canonical path, its content as read and
+/// with fonts replaced, and its layers.
+pub(crate) fn load_svg(path: &str) -> Result<LoadedSvg, Error> {
+    let full_path = fs::canonicalize(path).map_err(|e| Error::io(path, e))?;
+    let file_name = full_path.to_string_lossy().to_string();
+    let source = fs::read(&full_path).map_err(|e| Error::io(&file_name, e))?;
+    let svg_content = replace_fonts(source.clone())?;
+    let layers = get_layers(&svg_content)?;
+    Ok((file_name, source, svg_content, layers))
+}
@@ -0,0 +1,70 @@
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+use resvg::usvg;
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    // file name + cause
+    Io(String, Arc<io::Error>),
+    // output file name
+    MissingDirectory(String),
+    // byte position in the document + cause
+    Xml(u64, quick_xml::Error),
+    Svg(usvg::Error),
+    Render(String),
+    UnknownLayer(String),
+    UnknownPreset(String),
+    // file name + line number
+    Config(String, usize),
+    // output path written twice by a batch export
+    DuplicateOutput(String),
+    // what a batch export found none of, layers or presets
+    NothingToExport(&'static str),
+}
+
+impl Error {
+    pub(crate) fn io(file_name: &str, error: io::Error) -> Self {
+        Error::Io(file_name.to_string(), Arc::new(error))
+    }
+
+    pub(crate) fn xml(position: u64, error: impl Into<quick_xml::Error>) -> Self {
+        Error::Xml(position, error.into())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(file_name, e) if e.kind() == io::ErrorKind::NotFound => {
+                write!(f, "File not found: {file_name}")
+            }
+            Error::Io(file_name, e) => write!(f, "Unable to access {file_name}: {e}"),
+            Error::MissingDirectory(file_name) => {
+                write!(f, "Unable to write {file_name}: directory does not exist")
+            }
+            Error::Xml(position, e) => write!(f, "Malformed XML at position {position}: {e}"),
+            Error::Svg(e) => write!(f, "Unsupported SVG: {e}"),
+            Error::Render(e) => write!(f, "Unable to render: {e}"),
+            Error::UnknownLayer(name) => write!(f, "No layer named {name}"),
+            Error::UnknownPreset(name) => write!(f, "No preset named {name}"),
+            Error::Config(file_name, line) => {
+                write!(f, "Invalid configuration in {file_name}, line {line}")
+            }
+            Error::DuplicateOutput(output) => write!(
+                f,
+                "Several images would be written to {output}, add {{index}} to the name"
+            ),
+            Error::NothingToExport(what) => write!(f, "No {what} to export"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<usvg::Error> for Error {
+    fn from(error: usvg::Error) -> Self {
+        Error::Svg(error)
+    }
+}
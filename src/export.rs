@@ -1,44 +1,117 @@
 use resvg::usvg::{Size, Tree};
 use resvg::{tiny_skia, usvg};
-use std::io::prelude::*;
+use std::io::{self, prelude::*};
 use std::path::PathBuf;
+use std::sync::OnceLock;
 use std::{fs::File, sync::Arc};
 
-pub(crate) fn generate_svg_tree(svg_content: &[u8]) -> (Tree, Size) {
-    let mut fontdb = usvg::fontdb::Database::new();
-    fontdb.load_system_fonts();
+use crate::error::Error;
 
+// loading system fonts is slow, do it once for every parse
+fn fontdb() -> Arc<usvg::fontdb::Database> {
+    static FONTDB: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    FONTDB
+        .get_or_init(|| {
+            let mut fontdb = usvg::fontdb::Database::new();
+            fontdb.load_system_fonts();
+            Arc::new(fontdb)
+        })
+        .clone()
+}
+
+pub(crate) fn generate_svg_tree(svg_content: &[u8]) -> Result<(Tree, Size), Error> {
     let opt = usvg::Options {
-        fontdb: Arc::new(fontdb),
+        fontdb: fontdb(),
         ..Default::default()
     };
-    let tree = usvg::Tree::from_data(svg_content, &opt).unwrap();
+    let tree = usvg::Tree::from_data(svg_content, &opt)?;
 
     let pixmap_size = tree.size();
 
-    (tree, pixmap_size)
+    Ok((tree, pixmap_size))
 }
 
-pub(crate) fn generate_png(tree: &Tree, pixmap_size: &Size, scale: f32) -> Vec<u8> {
-    let mut pixmap =
-        tiny_skia::Pixmap::new(pixmap_size.width() as u32, pixmap_size.height() as u32).unwrap();
+pub(crate) fn generate_png(tree: &Tree, pixmap_size: &Size, scale: f32) -> Result<Vec<u8>, Error> {
+    let (width, height) = (pixmap_size.width() as u32, pixmap_size.height() as u32);
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| Error::Render(format!("invalid size {width}x{height}")))?;
     resvg::render(
         tree,
         tiny_skia::Transform::from_scale(scale, scale),
         &mut pixmap.as_mut(),
     );
 
-    pixmap.encode_png().unwrap()
+    pixmap
+        .encode_png()
+        .map_err(|e| Error::Render(e.to_string()))
+}
+
+/// Renders the tree into straight (non-premultiplied) RGBA pixels.
+pub(crate) fn generate_pixels(
+    tree: &Tree,
+    width: u32,
+    height: u32,
+    transform: tiny_skia::Transform,
+) -> Option<Vec<u8>> {
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)?;
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+
+    Some(
+        pixmap
+            .pixels()
+            .iter()
+            .flat_map(|p| {
+                let c = p.demultiply();
+                [c.red(), c.green(), c.blue(), c.alpha()]
+            })
+            .collect(),
+    )
+}
+
+/// Encodes opaque or premultiplied RGBA pixels as a PNG.
+pub(crate) fn encode_png(pixels: Vec<u8>, width: u32, height: u32) -> Result<Vec<u8>, Error> {
+    let pixmap = tiny_skia::IntSize::from_wh(width, height)
+        .and_then(|size| tiny_skia::Pixmap::from_vec(pixels, size))
+        .ok_or_else(|| Error::Render(format!("invalid size {width}x{height}")))?;
+    pixmap
+        .encode_png()
+        .map_err(|e| Error::Render(e.to_string()))
 }
 
-pub(crate) fn export_png(png_data: &[u8], svg_name: &String, overwrite: bool) -> Option<()> {
+pub(crate) fn export_png(
+    png_data: &[u8],
+    svg_name: &String,
+    overwrite: bool,
+) -> Result<Option<()>, Error> {
     let mut output_path = PathBuf::from(&svg_name);
-    output_path.set_extension("png");
+    let extension = output_path.extension().unwrap_or_default();
+    if !extension.eq_ignore_ascii_case("png") {
+        output_path.set_extension("png");
+    }
+    write_output(png_data, output_path, overwrite)
+}
+
+pub(crate) fn export_svg(
+    svg_data: &[u8],
+    svg_name: &String,
+    overwrite: bool,
+) -> Result<Option<()>, Error> {
+    let mut output_path = PathBuf::from(&svg_name);
+    output_path.set_extension("svg");
+    write_output(svg_data, output_path, overwrite)
+}
+
+fn write_output(data: &[u8], output_path: PathBuf, overwrite: bool) -> Result<Option<()>, Error> {
     if output_path.exists() && !overwrite {
-        None
+        Ok(None)
     } else {
-        let mut file = File::create(output_path).unwrap();
-        file.write_all(png_data).unwrap();
-        Some(())
+        let file_name = output_path.to_string_lossy();
+        let mut file = File::create(&output_path).map_err(|e| match e.kind() {
+            // the output itself is created, only its directory can be missing
+            io::ErrorKind::NotFound => Error::MissingDirectory(file_name.to_string()),
+            _ => Error::io(&file_name, e),
+        })?;
+        file.write_all(data).map_err(|e| Error::io(&file_name, e))?;
+        Ok(Some(()))
     }
 }
@@ -1,72 +1,207 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+
 use iced::{
-    advanced::{svg, Widget},
-    widget::Svg,
-    Element,
+    advanced::{
+        image::{self, FilterMethod},
+        layout,
+        widget::tree,
+        Widget,
+    },
+    Element, Length, Rectangle,
 };
+use resvg::usvg::Tree;
 
-pub(crate) struct ExtendedSvg<Theme = iced_style::Theme>
-where
-    Theme: iced_style::svg::StyleSheet,
-{
-    pub inner: Svg<Theme>,
+use crate::diff::diff_pixels;
+use crate::export::generate_pixels;
+use crate::transform::{view_transform, Matrix};
+
+/// Draws a parsed SVG tree, applying the zoom/pan matrix at draw time instead
+/// of rewriting and re-parsing the document.
+pub(crate) struct ExtendedSvg<Message> {
+    svg_tree: Arc<Tree>,
+    // when set, the pixel difference from this tree is drawn instead
+    diff_tree: Option<Arc<Tree>>,
+    matrix_transform: Matrix,
+    // physical pixels per logical pixel, rendering sharp on HiDPI screens
+    scale_factor: f32,
+    width: Length,
+    height: Length,
+    on_bounds: Option<fn(Rectangle) -> Message>,
+}
+
+impl<Message> ExtendedSvg<Message> {
+    pub(crate) fn new(svg_tree: Arc<Tree>, matrix_transform: Matrix) -> Self {
+        ExtendedSvg {
+            svg_tree,
+            diff_tree: None,
+            matrix_transform,
+            scale_factor: 1.0,
+            width: Length::Fill,
+            height: Length::Fill,
+            on_bounds: None,
+        }
+    }
+
+    pub(crate) fn width(mut self, width: Length) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub(crate) fn height(mut self, height: Length) -> Self {
+        self.height = height;
+        self
+    }
+
+    pub(crate) fn scale_factor(mut self, scale_factor: f32) -> Self {
+        self.scale_factor = scale_factor;
+        self
+    }
+
+    /// Draws the changes from the given tree to this one, highlighted.
+    pub(crate) fn diff(mut self, before: Arc<Tree>) -> Self {
+        self.diff_tree = Some(before);
+        self
+    }
+
+    /// Sets the message published whenever the widget bounds change.
+    pub(crate) fn on_bounds(mut self, on_bounds: fn(Rectangle) -> Message) -> Self {
+        self.on_bounds = Some(on_bounds);
+        self
+    }
+}
+
+// trees, matrix and size of the last rendering, the trees being kept alive so
+// that a new tree cannot get the address of a dropped one
+struct CacheKey {
+    svg_tree: Arc<Tree>,
+    diff_tree: Option<Arc<Tree>>,
+    matrix_transform: Matrix,
+    size: (u32, u32),
 }
 
-impl<Message, Theme, Renderer> Widget<Message, Theme, Renderer> for ExtendedSvg<Theme>
+impl CacheKey {
+    fn matches<Message>(&self, svg: &ExtendedSvg<Message>, size: (u32, u32)) -> bool {
+        let same_diff = match (&self.diff_tree, &svg.diff_tree) {
+            (Some(cached), Some(tree)) => Arc::ptr_eq(cached, tree),
+            (None, None) => true,
+            _ => false,
+        };
+        Arc::ptr_eq(&self.svg_tree, &svg.svg_tree)
+            && same_diff
+            && self.matrix_transform == svg.matrix_transform
+            && self.size == size
+    }
+}
+
+#[derive(Default)]
+struct State {
+    // the last rendering, reused as long as nothing changed
+    cache: RefCell<Option<(CacheKey, image::Handle)>>,
+    // the last bounds reported to the application
+    bounds: Option<Rectangle>,
+}
+
+impl<Message, Theme, Renderer> Widget<Message, Theme, Renderer> for ExtendedSvg<Message>
 where
-    Theme: iced_style::svg::StyleSheet,
-    Renderer: svg::Renderer,
+    Renderer: image::Renderer<Handle = image::Handle>,
 {
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
     fn size(&self) -> iced::Size<iced::Length> {
-        <Svg<Theme> as Widget<Message, Theme, Renderer>>::size(&self.inner)
+        iced::Size::new(self.width, self.height)
     }
 
     fn layout(
         &self,
-        tree: &mut iced::advanced::widget::Tree,
-        renderer: &Renderer,
+        _tree: &mut iced::advanced::widget::Tree,
+        _renderer: &Renderer,
         limits: &iced::advanced::layout::Limits,
     ) -> iced::advanced::layout::Node {
-        <Svg<Theme> as Widget<Message, Theme, Renderer>>::layout(
-            &self.inner,
-            tree,
-            renderer,
-            limits,
-        )
+        layout::atomic(limits, self.width, self.height)
     }
 
     fn draw(
         &self,
         tree: &iced::advanced::widget::Tree,
         renderer: &mut Renderer,
-        theme: &Theme,
-        style: &iced::advanced::renderer::Style,
+        _theme: &Theme,
+        _style: &iced::advanced::renderer::Style,
         layout: iced::advanced::Layout<'_>,
-        cursor: iced::advanced::mouse::Cursor,
-        viewport: &iced::Rectangle,
+        _cursor: iced::advanced::mouse::Cursor,
+        _viewport: &iced::Rectangle,
     ) {
-        <Svg<Theme> as Widget<Message, Theme, Renderer>>::draw(
-            &self.inner,
-            tree,
-            renderer,
-            theme,
-            style,
-            layout,
-            cursor,
-            viewport,
-        )
+        let bounds = layout.bounds();
+        // rendered in physical pixels, drawn in the logical bounds
+        let (physical_width, physical_height) = (
+            bounds.width * self.scale_factor,
+            bounds.height * self.scale_factor,
+        );
+        let (width, height) = (physical_width as u32, physical_height as u32);
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let state = tree.state.downcast_ref::<State>();
+        let mut cache = state.cache.borrow_mut();
+        let handle = match cache.as_ref() {
+            Some((key, handle)) if key.matches(self, (width, height)) => handle.clone(),
+            _ => {
+                let render = |tree: &Tree| {
+                    let transform = view_transform(
+                        self.matrix_transform,
+                        tree.size(),
+                        (physical_width, physical_height),
+                    );
+                    generate_pixels(tree, width, height, transform)
+                };
+                let Some(mut pixels) = render(&self.svg_tree) else {
+                    return;
+                };
+                if let Some(before) = self.diff_tree.as_ref().and_then(|tree| render(tree)) {
+                    pixels = diff_pixels(&before, &pixels).pixels;
+                }
+                let handle = image::Handle::from_pixels(width, height, pixels);
+                let key = CacheKey {
+                    svg_tree: self.svg_tree.clone(),
+                    diff_tree: self.diff_tree.clone(),
+                    matrix_transform: self.matrix_transform,
+                    size: (width, height),
+                };
+                *cache = Some((key, handle.clone()));
+                handle
+            }
+        };
+
+        renderer.draw(handle, FilterMethod::Linear, bounds);
     }
 
     fn on_event(
         &mut self,
-        _state: &mut iced::advanced::widget::Tree,
+        tree: &mut iced::advanced::widget::Tree,
         _event: iced::Event,
         layout: iced::advanced::Layout<'_>,
         cursor: iced::advanced::mouse::Cursor,
         _renderer: &Renderer,
         _clipboard: &mut dyn iced::advanced::Clipboard,
-        _shell: &mut iced::advanced::Shell<'_, Message>,
+        shell: &mut iced::advanced::Shell<'_, Message>,
         _viewport: &iced::Rectangle,
     ) -> iced_style::core::event::Status {
+        let state = tree.state.downcast_mut::<State>();
+        let bounds = layout.bounds();
+        if state.bounds != Some(bounds) {
+            state.bounds = Some(bounds);
+            if let Some(on_bounds) = self.on_bounds {
+                shell.publish(on_bounds(bounds));
+            }
+        }
+
         let position = cursor.position();
         if position.is_some() && layout.bounds().contains(position.unwrap()) {
             iced::event::Status::Captured
@@ -76,13 +211,13 @@ where
     }
 }
 
-impl<'a, Message, Theme, Renderer> From<ExtendedSvg<Theme>>
+impl<'a, Message, Theme, Renderer> From<ExtendedSvg<Message>>
     for Element<'a, Message, Theme, Renderer>
 where
-    Theme: iced_style::svg::StyleSheet + 'a,
-    Renderer: svg::Renderer + 'a,
+    Message: 'a,
+    Renderer: image::Renderer<Handle = image::Handle> + 'a,
 {
-    fn from(icon: ExtendedSvg<Theme>) -> Element<'a, Message, Theme, Renderer> {
+    fn from(icon: ExtendedSvg<Message>) -> Element<'a, Message, Theme, Renderer> {
         Element::new(icon)
     }
 }
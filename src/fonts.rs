@@ -5,34 +5,36 @@ use quick_xml::events::attributes::Attribute;
 use quick_xml::events::{BytesStart, Event};
 use quick_xml::{Reader, Writer};
 
-pub(crate) fn replace_fonts(svg_content: Vec<u8>) -> Vec<u8> {
+use crate::error::Error;
+
+pub(crate) fn replace_fonts(svg_content: Vec<u8>) -> Result<Vec<u8>, Error> {
     let mut reader = Reader::from_reader(svg_content.as_slice());
     reader.config_mut().trim_text(true);
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     loop {
-        match reader.read_event() {
+        let event = match reader.read_event() {
             Ok(Event::Start(e)) if e.name().as_ref() == b"text" => {
                 let mut current_text = BytesStart::new("text");
-                let current_attributes = e.attributes();
-                current_text.extend_attributes(current_attributes.map(|a| {
-                    let attr = a.unwrap();
+                for attr in e.attributes() {
+                    let attr = attr.map_err(|e| Error::xml(reader.buffer_position(), e))?;
                     if attr.key.as_ref() == b"font-family" {
-                        convert_font(attr)
+                        current_text.push_attribute(convert_font(attr))
                     } else {
-                        attr
+                        current_text.push_attribute(attr)
                     }
-                }));
-                writer.write_event(Event::Start(current_text)).unwrap()
+                }
+                Event::Start(current_text)
             }
             Ok(Event::Eof) => break,
-            Ok(e) => {
-                writer.write_event(e).unwrap();
-            }
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
-        }
+            Ok(e) => e,
+            Err(e) => return Err(Error::xml(reader.error_position(), e)),
+        };
+        writer
+            .write_event(event)
+            .map_err(|e| Error::xml(reader.buffer_position(), e))?;
     }
 
-    writer.into_inner().into_inner()
+    Ok(writer.into_inner().into_inner())
 }
 
 fn convert_font(attr: Attribute) -> Attribute {
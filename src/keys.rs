@@ -0,0 +1,251 @@
+use iced::keyboard::key::Named;
+use iced::keyboard::{Key, Modifiers};
+
+use crate::config::read_config;
+use crate::error::Error;
+
+/// Name of the key bindings file in the configuration directory, holding one
+/// `action = shortcut` binding per line, such as `export = ctrl+e`.
+pub(crate) const KEYS_FILE: &str = "keys.conf";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Action {
+    ZoomIn,
+    ZoomOut,
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    Reset,
+    Export,
+    Open,
+    Reload,
+    // index of the layer, in document order
+    ToggleLayer(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Shortcut {
+    // the command key on macOS
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+    // lowercase character, or name of a named key
+    key: String,
+}
+
+impl Shortcut {
+    pub(crate) fn matches(&self, key: &Key, modifiers: Modifiers) -> bool {
+        let (name, shift) = match key.as_ref() {
+            // the character already tells whether shift is down, "+" is often shift+"=",
+            // so shift only matters when the shortcut asks for it
+            Key::Character(c) => (c.to_lowercase(), self.shift && modifiers.shift()),
+            Key::Named(named) => match named_key(named) {
+                Some(name) => (name.to_string(), modifiers.shift()),
+                None => return false,
+            },
+            Key::Unidentified => return false,
+        };
+        name == self.key
+            && self.ctrl == modifiers.command()
+            && self.alt == modifiers.alt()
+            && self.shift == shift
+    }
+}
+
+pub(crate) type Bindings = Vec<(Shortcut, Action)>;
+
+pub(crate) fn default_bindings() -> Bindings {
+    let mut bindings: Bindings = [
+        ("+", Action::ZoomIn),
+        ("=", Action::ZoomIn),
+        ("-", Action::ZoomOut),
+        ("left", Action::PanLeft),
+        ("right", Action::PanRight),
+        ("up", Action::PanUp),
+        ("down", Action::PanDown),
+        ("0", Action::Reset),
+        ("ctrl+e", Action::Export),
+        ("ctrl+o", Action::Open),
+        ("ctrl+r", Action::Reload),
+    ]
+    .into_iter()
+    .filter_map(|(shortcut, action)| Some((parse_shortcut(shortcut)?, action)))
+    .collect();
+    for layer in 0..9 {
+        let shortcut = parse_shortcut(&(layer + 1).to_string());
+        bindings.extend(shortcut.map(|s| (s, Action::ToggleLayer(layer))));
+    }
+    bindings
+}
+
+/// The default bindings, with the ones of the configuration file replacing
+/// the defaults of the same actions.
+pub(crate) fn load_bindings() -> Result<Bindings, Error> {
+    let mut bindings = default_bindings();
+    let Some((path, content)) = read_config(KEYS_FILE)? else {
+        return Ok(bindings);
+    };
+
+    let mut configured: Vec<Action> = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let binding = line.split_once('=').and_then(|(action, shortcut)| {
+            Some((
+                parse_action(action.trim())?,
+                parse_shortcut(shortcut.trim())?,
+            ))
+        });
+        let Some((action, shortcut)) = binding else {
+            return Err(Error::Config(path.to_string_lossy().into(), number + 1));
+        };
+        if !configured.contains(&action) {
+            configured.push(action);
+            bindings.retain(|(_, a)| *a != action);
+        }
+        bindings.push((shortcut, action));
+    }
+    Ok(bindings)
+}
+
+fn parse_action(action: &str) -> Option<Action> {
+    let action = match action {
+        "zoom-in" => Action::ZoomIn,
+        "zoom-out" => Action::ZoomOut,
+        "pan-left" => Action::PanLeft,
+        "pan-right" => Action::PanRight,
+        "pan-up" => Action::PanUp,
+        "pan-down" => Action::PanDown,
+        "reset" => Action::Reset,
+        "export" => Action::Export,
+        "open" => Action::Open,
+        "reload" => Action::Reload,
+        _ => {
+            let layer: usize = action.strip_prefix("toggle-layer-")?.parse().ok()?;
+            Action::ToggleLayer(layer.checked_sub(1)?)
+        }
+    };
+    Some(action)
+}
+
+/// Parses shortcuts such as `ctrl+shift+e`, `alt+left` or `ctrl++`.
+fn parse_shortcut(shortcut: &str) -> Option<Shortcut> {
+    let shortcut = shortcut.to_lowercase();
+    let (modifiers, key) = match shortcut.strip_suffix("++") {
+        Some(modifiers) => (Some(modifiers), "+"),
+        None => match shortcut.rsplit_once('+') {
+            Some((modifiers, key)) if !key.is_empty() => (Some(modifiers), key),
+            _ => (None, shortcut.as_str()),
+        },
+    };
+
+    let key = match key {
+        "plus" => "+",
+        "minus" => "-",
+        key => key,
+    };
+    if key.chars().count() != 1 && !NAMED_KEYS.iter().any(|(_, name)| *name == key) {
+        return None;
+    }
+
+    let mut parsed = Shortcut {
+        ctrl: false,
+        alt: false,
+        shift: false,
+        key: key.to_string(),
+    };
+    for modifier in modifiers.into_iter().flat_map(|m| m.split('+')) {
+        match modifier.trim() {
+            "ctrl" | "cmd" => parsed.ctrl = true,
+            "alt" => parsed.alt = true,
+            "shift" => parsed.shift = true,
+            _ => return None,
+        }
+    }
+    Some(parsed)
+}
+
+const NAMED_KEYS: [(Named, &str); 13] = [
+    (Named::ArrowLeft, "left"),
+    (Named::ArrowRight, "right"),
+    (Named::ArrowUp, "up"),
+    (Named::ArrowDown, "down"),
+    (Named::Home, "home"),
+    (Named::End, "end"),
+    (Named::PageUp, "pageup"),
+    (Named::PageDown, "pagedown"),
+    (Named::Space, "space"),
+    (Named::Enter, "enter"),
+    (Named::Tab, "tab"),
+    (Named::Escape, "escape"),
+    (Named::Delete, "delete"),
+];
+
+fn named_key(named: Named) -> Option<&'static str> {
+    NAMED_KEYS
+        .iter()
+        .find(|(key, _)| *key == named)
+        .map(|(_, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn character(c: &str) -> Key {
+        Key::Character(c.into())
+    }
+
+    #[test]
+    fn parses_modifiers_and_keys() {
+        let shortcut = parse_shortcut("Ctrl+Shift+E").unwrap();
+        assert!(shortcut.ctrl && shortcut.shift && !shortcut.alt);
+        assert_eq!(shortcut.key, "e");
+        assert_eq!(parse_shortcut("alt+left").unwrap().key, "left");
+        assert!(parse_shortcut("ctrl+f13").is_none());
+        assert!(parse_shortcut("hyper+e").is_none());
+    }
+
+    #[test]
+    fn parses_plus_as_a_key() {
+        let shortcut = parse_shortcut("ctrl++").unwrap();
+        assert!(shortcut.ctrl);
+        assert_eq!(shortcut.key, "+");
+        assert_eq!(parse_shortcut("+").unwrap().key, "+");
+        assert_eq!(parse_shortcut("ctrl+plus"), parse_shortcut("ctrl++"));
+        assert_eq!(parse_shortcut("minus").unwrap().key, "-");
+    }
+
+    #[test]
+    fn matches_characters_typed_with_shift() {
+        // "+" is shift+"=" on most layouts
+        let plus = parse_shortcut("+").unwrap();
+        assert!(plus.matches(&character("+"), Modifiers::SHIFT));
+        assert!(plus.matches(&character("+"), Modifiers::empty()));
+        let shift_e = parse_shortcut("shift+e").unwrap();
+        assert!(shift_e.matches(&character("E"), Modifiers::SHIFT));
+        assert!(!shift_e.matches(&character("e"), Modifiers::empty()));
+    }
+
+    #[test]
+    fn matches_modifiers_exactly() {
+        let export = parse_shortcut("ctrl+e").unwrap();
+        assert!(export.matches(&character("e"), Modifiers::COMMAND));
+        assert!(!export.matches(&character("e"), Modifiers::empty()));
+        assert!(!export.matches(&character("e"), Modifiers::COMMAND | Modifiers::ALT));
+        let left = parse_shortcut("left").unwrap();
+        assert!(left.matches(&Key::Named(Named::ArrowLeft), Modifiers::empty()));
+        assert!(!left.matches(&Key::Named(Named::ArrowLeft), Modifiers::SHIFT));
+    }
+
+    #[test]
+    fn parses_actions() {
+        assert_eq!(parse_action("zoom-in"), Some(Action::ZoomIn));
+        assert_eq!(parse_action("toggle-layer-1"), Some(Action::ToggleLayer(0)));
+        assert_eq!(parse_action("toggle-layer-0"), None);
+        assert_eq!(parse_action("explode"), None);
+    }
+}
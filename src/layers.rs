@@ -1,88 +1,289 @@
 use std::borrow::Cow;
 use std::io::Cursor;
 
-use quick_xml::events::attributes::Attribute;
+use quick_xml::events::attributes::{AttrError, Attribute};
 use quick_xml::events::{BytesStart, Event};
 use quick_xml::{Reader, Writer};
 
-pub(crate) fn get_layers(svg_content: &Vec<u8>) -> Vec<String> {
-    let mut reader = Reader::from_reader(svg_content.as_slice());
+use crate::error::Error;
+
+/// Identifies a layer across rewrites of the document: its `id` attribute when
+/// present, otherwise its position among the layers of the document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LayerKey {
+    Id(String),
+    Position(usize),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Layer {
+    pub(crate) key: LayerKey,
+    pub(crate) name: String,
+    // index of the enclosing layer, if any
+    pub(crate) parent: Option<usize>,
+    pub(crate) depth: usize,
+    pub(crate) visible: bool,
+    // from 0, transparent, to 1, opaque
+    pub(crate) opacity: f32,
+    pub(crate) expanded: bool,
+}
+
+/// Shows the layer alone: its enclosing layers and its sublayers stay shown,
+/// every other layer is hidden.
+pub(crate) fn solo_layer(layers: &mut [Layer], index: usize) {
+    let encloses = |mut layer: usize, ancestor: usize| loop {
+        if layer == ancestor {
+            return true;
+        }
+        match layers[layer].parent {
+            Some(parent) => layer = parent,
+            None => return false,
+        }
+    };
+    let visible: Vec<bool> = (0..layers.len())
+        .map(|i| encloses(index, i) || encloses(i, index))
+        .collect();
+    for (layer, visible) in layers.iter_mut().zip(visible) {
+        layer.visible = visible;
+    }
+}
+
+pub(crate) fn get_layers(svg_content: &[u8]) -> Result<Vec<Layer>, Error> {
+    let mut reader = Reader::from_reader(svg_content);
     reader.config_mut().trim_text(true);
 
-    let mut groups: Vec<BytesStart> = Vec::new();
+    // one entry per open group, holding the layer index if the group is a layer
+    let mut open_groups: Vec<Option<usize>> = Vec::new();
+    let mut layers: Vec<Layer> = Vec::new();
 
     loop {
         match reader.read_event() {
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
+            Err(e) => return Err(Error::xml(reader.error_position(), e)),
             // exits the loop when reaching end of file
             Ok(Event::Eof) => break,
 
-            Ok(Event::Start(e)) => {
-                if e.name().as_ref() == b"g" {
-                    groups.push(e)
-                }
+            Ok(Event::Start(e)) if e.name().as_ref() == b"g" => {
+                let index = push_layer(&e, &open_groups, &mut layers);
+                open_groups.push(index);
+            }
+            Ok(Event::Empty(e)) if e.name().as_ref() == b"g" => {
+                push_layer(&e, &open_groups, &mut layers);
+            }
+            Ok(Event::End(e)) if e.name().as_ref() == b"g" => {
+                open_groups.pop();
             }
 
             _ => (),
         }
     }
 
-    let layers: Vec<String> = get_layers_from_groups(groups)
-        .iter()
-        .flat_map(|l| extract_layer_name(l))
-        .collect();
-
-    layers
+    Ok(layers)
 }
 
-pub(crate) fn set_visible_layers(svg_content: &[u8], layers: &[(String, bool)]) -> Vec<u8> {
+pub(crate) fn set_visible_layers(svg_content: &[u8], layers: &[Layer]) -> Result<Vec<u8>, Error> {
     let mut reader = Reader::from_reader(svg_content);
-    reader.config_mut().trim_text(true);
     let mut writer = Writer::new(Cursor::new(Vec::new()));
+    let mut position = 0;
     loop {
-        match reader.read_event() {
+        let event = match reader.read_event() {
             Ok(Event::Start(e)) if e.name().as_ref() == b"g" && is_layer(&e) => {
-                let mut current_layer = BytesStart::new("g");
-
-                let (_, visibility) = layers
-                    .iter()
-                    .find(|(l, _)| *l == extract_layer_name(&e).expect("layer with no name"))
-                    .expect("mismatched window-svg layers");
-                let attr_visibility = if *visibility { "visible" } else { "hidden" };
-
-                let has_visibility = e.attributes().any(|a| {
-                    let attr = a.as_ref().unwrap();
-                    attr.key.as_ref() == b"visibility"
-                });
-
-                let current_attributes = e.attributes();
-                if !has_visibility {
-                    current_layer.extend_attributes(current_attributes.map(|a| a.unwrap()));
-                    current_layer.push_attribute(("visibility", attr_visibility));
-                } else {
-                    current_layer.extend_attributes(current_attributes.map(|a| {
-                        let attr = a.unwrap();
-                        if attr.key.as_ref() == b"visibility" {
-                            Attribute {
-                                key: attr.key,
-                                value: Cow::from(attr_visibility.as_bytes()),
-                            }
-                        } else {
-                            attr
-                        }
-                    }));
-                }
-                writer.write_event(Event::Start(current_layer)).unwrap()
+                let current_layer = with_visibility(&e, find_layer(&e, position, layers))
+                    .map_err(|e| Error::xml(reader.buffer_position(), e))?;
+                position += 1;
+                Event::Start(current_layer)
+            }
+            Ok(Event::Empty(e)) if e.name().as_ref() == b"g" && is_layer(&e) => {
+                let current_layer = with_visibility(&e, find_layer(&e, position, layers))
+                    .map_err(|e| Error::xml(reader.buffer_position(), e))?;
+                position += 1;
+                Event::Empty(current_layer)
             }
             Ok(Event::Eof) => break,
-            Ok(e) => {
-                writer.write_event(e).unwrap();
+            Ok(e) => e,
+            Err(e) => return Err(Error::xml(reader.error_position(), e)),
+        };
+        writer
+            .write_event(event)
+            .map_err(|e| Error::xml(reader.buffer_position(), e))?;
+    }
+
+    Ok(writer.into_inner().into_inner())
+}
+
+/// Drops hidden layers, along with everything they contain, from the document.
+pub(crate) fn remove_hidden_layers(svg_content: &[u8], layers: &[Layer]) -> Result<Vec<u8>, Error> {
+    let mut reader = Reader::from_reader(svg_content);
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    let mut position = 0;
+    // nesting depth inside a hidden layer, zero outside of one
+    let mut hidden_depth = 0;
+    loop {
+        let event = match reader.read_event() {
+            Ok(Event::Eof) => break,
+            Ok(e) => e,
+            Err(e) => return Err(Error::xml(reader.error_position(), e)),
+        };
+        let hidden = match &event {
+            Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"g" && is_layer(e) => {
+                let layer = find_layer(e, position, layers);
+                position += 1;
+                layer.is_some_and(|l| !l.visible)
+            }
+            _ => false,
+        };
+        match event {
+            Event::Start(_) if hidden || hidden_depth > 0 => hidden_depth += 1,
+            Event::End(_) if hidden_depth > 0 => hidden_depth -= 1,
+            Event::Empty(_) if hidden => (),
+            _ if hidden_depth > 0 => (),
+            e => writer
+                .write_event(e)
+                .map_err(|e| Error::xml(reader.buffer_position(), e))?,
+        }
+    }
+
+    Ok(writer.into_inner().into_inner())
+}
+
+fn push_layer(
+    group: &BytesStart,
+    open_groups: &[Option<usize>],
+    layers: &mut Vec<Layer>,
+) -> Option<usize> {
+    if !is_layer(group) {
+        return None;
+    }
+    let position = layers.len();
+    let key = layer_key(group, position);
+    let name = extract_attribute(group, b"inkscape:label")
+        .or_else(|| extract_attribute(group, b"id"))
+        .unwrap_or_else(|| format!("Layer {}", position + 1));
+    let parent = open_groups.iter().rev().find_map(|g| *g);
+    let depth = parent.map_or(0, |p| layers[p].depth + 1);
+    layers.push(Layer {
+        key,
+        name,
+        parent,
+        depth,
+        visible: !is_hidden(group),
+        opacity: opacity(group),
+        expanded: false,
+    });
+    Some(position)
+}
+
+fn find_layer<'a>(group: &BytesStart, position: usize, layers: &'a [Layer]) -> Option<&'a Layer> {
+    let key = layer_key(group, position);
+    layers.iter().find(|l| l.key == key)
+}
+
+fn with_visibility(
+    e: &BytesStart,
+    layer: Option<&Layer>,
+) -> Result<BytesStart<'static>, AttrError> {
+    let Some(layer) = layer else {
+        // unknown layer, leave it untouched
+        return Ok(e.to_owned());
+    };
+
+    // display:none on a layer also hides its sublayers, which keep their own state
+    let shown = layer.visible;
+    // the opacity is only written once changed, leaving the document as it is
+    let set_opacity = layer.opacity != opacity(e);
+
+    let mut current_layer = BytesStart::new("g");
+    let mut has_style = false;
+    for attr in e.attributes() {
+        let attr = attr?;
+        match attr.key.as_ref() {
+            b"style" => {
+                has_style = true;
+                let style =
+                    merge_display(&String::from_utf8_lossy(&attr.value), shown, set_opacity);
+                if !style.is_empty() {
+                    current_layer.push_attribute(Attribute {
+                        key: attr.key,
+                        value: Cow::from(style.into_bytes()),
+                    });
+                }
             }
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
+            // hidden by the document itself, drop it so the layer can be shown
+            b"display" | b"visibility" if shown => (),
+            // replaced below
+            b"opacity" if set_opacity => (),
+            _ => current_layer.push_attribute(attr),
         }
     }
+    if !has_style && !shown {
+        current_layer.push_attribute(("style", "display:none"));
+    }
+    if set_opacity && layer.opacity < 1.0 {
+        current_layer.push_attribute(("opacity", layer.opacity.to_string().as_str()));
+    }
+    Ok(current_layer.into_owned())
+}
+
+/// Replaces the `display` declaration of a style, keeping everything else, the
+/// same way Inkscape hides and shows layers. A new opacity goes to the
+/// attribute, dropping the declaration that would override it.
+fn merge_display(style: &str, shown: bool, set_opacity: bool) -> String {
+    let mut declarations: Vec<&str> = style
+        .split(';')
+        .filter(|d| match parse_declaration(d) {
+            Some(("display", _)) => false,
+            Some(("opacity", _)) if set_opacity => false,
+            Some(decl) => !(shown && hides(decl)),
+            None => !d.trim().is_empty(),
+        })
+        .collect();
+    if !shown {
+        declarations.push("display:none");
+    }
+    declarations.join(";")
+}
+
+/// Whether the document itself hides the group, as Inkscape does for hidden
+/// layers, either through its style or through presentation attributes.
+fn is_hidden(group: &BytesStart) -> bool {
+    let style = extract_attribute(group, b"style").unwrap_or_default();
+    let hidden_by_style = style.split(';').flat_map(parse_declaration).any(hides);
+    let hidden_by_attribute = ["display", "visibility"].into_iter().any(|property| {
+        extract_attribute(group, property.as_bytes())
+            .is_some_and(|value| hides((property, value.trim())))
+    });
+    hidden_by_style || hidden_by_attribute
+}
+
+/// The opacity of the group, from its style as Inkscape writes it, or from its
+/// attribute.
+fn opacity(group: &BytesStart) -> f32 {
+    let style = extract_attribute(group, b"style").unwrap_or_default();
+    let value = style
+        .split(';')
+        .flat_map(parse_declaration)
+        .find(|(property, _)| *property == "opacity")
+        .map(|(_, value)| value.to_string())
+        .or_else(|| extract_attribute(group, b"opacity"));
+    let opacity = match value.as_deref().map(str::trim) {
+        Some(value) => match value.strip_suffix('%') {
+            Some(percentage) => percentage.parse().map(|p: f32| p / 100.0),
+            None => value.parse(),
+        },
+        None => Ok(1.0),
+    };
+    opacity.unwrap_or(1.0).clamp(0.0, 1.0)
+}
+
+fn parse_declaration(declaration: &str) -> Option<(&str, &str)> {
+    let (property, value) = declaration.split_once(':')?;
+    Some((property.trim(), value.trim()))
+}
 
-    writer.into_inner().into_inner()
+fn hides((property, value): (&str, &str)) -> bool {
+    matches!(
+        (property, value),
+        ("display", "none") | ("visibility", "hidden") | ("visibility", "collapse")
+    )
 }
 
 fn is_layer(group: &BytesStart) -> bool {
@@ -92,19 +293,122 @@ fn is_layer(group: &BytesStart) -> bool {
     })
 }
 
-fn get_layers_from_groups(groups: Vec<BytesStart>) -> Vec<BytesStart> {
-    groups.into_iter().filter(|g| is_layer(g)).collect()
+fn layer_key(group: &BytesStart, position: usize) -> LayerKey {
+    match extract_attribute(group, b"id") {
+        Some(id) => LayerKey::Id(id),
+        None => LayerKey::Position(position),
+    }
 }
 
-fn extract_layer_name(event: &BytesStart) -> Option<String> {
-    let name = event
+fn extract_attribute(event: &BytesStart, key: &[u8]) -> Option<String> {
+    let value = event
         .attributes()
         .find(|a| match a {
             Err(_) => false,
-            Ok(attr) => attr.key.as_ref() == b"inkscape:label",
+            Ok(attr) => attr.key.as_ref() == key,
         })?
         .ok()?;
-    String::from_utf8(name.value.as_ref().to_owned())
+    String::from_utf8(value.value.as_ref().to_owned())
         .map(|s| s.replace("&quot;", "\""))
         .ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVG: &[u8] = br#"<svg xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape">
+  <g inkscape:groupmode="layer" id="base" inkscape:label="Base" style="fill:red;display:none">
+    <g inkscape:groupmode="layer" inkscape:label="Notes" visibility="hidden"/>
+  </g>
+  <g inkscape:groupmode="layer" id="top" style="opacity:0.5"><rect/></g>
+</svg>"#;
+
+    #[test]
+    fn merges_display_into_the_style() {
+        assert_eq!(
+            merge_display("fill:red", false, false),
+            "fill:red;display:none"
+        );
+        assert_eq!(
+            merge_display("fill:red; display : none", true, false),
+            "fill:red"
+        );
+        assert_eq!(
+            merge_display("visibility:hidden;fill:red", true, false),
+            "fill:red"
+        );
+        assert_eq!(
+            merge_display("opacity:0.5;fill:red", true, false),
+            "opacity:0.5;fill:red"
+        );
+        assert_eq!(
+            merge_display("opacity:0.5;fill:red", true, true),
+            "fill:red"
+        );
+        assert_eq!(merge_display("", true, false), "");
+    }
+
+    #[test]
+    fn tells_hidden_groups() {
+        let group = |content| BytesStart::from_content(content, 1);
+        assert!(is_hidden(&group(r#"g style="fill:red;display:none""#)));
+        assert!(is_hidden(&group(r#"g visibility="hidden""#)));
+        assert!(is_hidden(&group(r#"g display=" none ""#)));
+        assert!(!is_hidden(&group(r#"g style="display:inline""#)));
+        assert!(!is_hidden(&group("g")));
+    }
+
+    #[test]
+    fn reads_nested_layers() {
+        let layers = get_layers(SVG).unwrap();
+        assert_eq!(layers.len(), 3);
+        assert_eq!(layers[0].name, "Base");
+        assert!(!layers[0].visible);
+        assert_eq!((layers[1].parent, layers[1].depth), (Some(0), 1));
+        assert_eq!(layers[1].key, LayerKey::Position(1));
+        assert!(!layers[1].visible);
+        assert_eq!(layers[2].opacity, 0.5);
+    }
+
+    #[test]
+    fn shows_and_hides_layers() {
+        let mut layers = get_layers(SVG).unwrap();
+        layers[0].visible = true;
+        layers[1].visible = true;
+        layers[2].visible = false;
+        let svg_content = set_visible_layers(SVG, &layers).unwrap();
+        let rewritten = get_layers(&svg_content).unwrap();
+        let visible: Vec<bool> = rewritten.iter().map(|l| l.visible).collect();
+        assert_eq!(visible, vec![true, true, false]);
+        assert_eq!(rewritten[2].opacity, 0.5);
+    }
+
+    #[test]
+    fn rewrites_layers_once() {
+        let mut layers = get_layers(SVG).unwrap();
+        layers[0].visible = true;
+        layers[2].opacity = 0.25;
+        let once = String::from_utf8(set_visible_layers(SVG, &layers).unwrap()).unwrap();
+        let twice = set_visible_layers(once.as_bytes(), &layers).unwrap();
+        assert_eq!(String::from_utf8(twice).unwrap(), once);
+    }
+
+    #[test]
+    fn removes_hidden_layers_with_their_content() {
+        let mut layers = get_layers(SVG).unwrap();
+        layers[2].visible = false;
+        let svg_content = set_visible_layers(SVG, &layers).unwrap();
+        let stripped = remove_hidden_layers(&svg_content, &layers).unwrap();
+        assert!(get_layers(&stripped).unwrap().is_empty());
+        assert!(!String::from_utf8(stripped).unwrap().contains("<rect"));
+    }
+
+    #[test]
+    fn solos_a_layer_with_its_enclosing_layers() {
+        let mut layers = get_layers(SVG).unwrap();
+        solo_layer(&mut layers, 1);
+        let visible: Vec<bool> = layers.iter().map(|l| l.visible).collect();
+        assert_eq!(visible, vec![true, true, false]);
+    }
+}
@@ -1,37 +1,70 @@
+mod batch;
+mod cli;
+mod config;
+mod diff;
+mod document;
+mod error;
 mod export;
 mod ext_svg;
 mod fonts;
+mod keys;
 mod layers;
+mod presets;
 mod transform;
 mod view;
 
-use fonts::replace_fonts;
+use document::Document;
 use iced::{Application, Settings};
-use layers::get_layers;
-use std::io::Read;
-use std::{env, fs, vec};
+use std::{env, process};
 use view::{Picture, PictureFlags};
 
 pub fn main() -> iced::Result {
     let args: Vec<_> = env::args().collect();
-    let (svg_content, file_name): (Vec<u8>, String) = if args.len() > 1 {
-        let full_path = fs::canonicalize(&args[1]).unwrap();
-        let file_name = full_path.into_os_string().into_string().unwrap();
-        let mut file = fs::File::open(&file_name).expect("unable to open file");
-        let metadata = fs::metadata(&file_name).expect("unable to read metadata");
-        let mut buffer = vec![0; metadata.len() as usize];
-        file.read_exact(&mut buffer).expect("buffer overflow");
-        (buffer, file_name.to_string())
-    } else {
-        (Vec::new(), String::new())
-    };
+    if args.len() > 1 && args[1] == "export" {
+        let export_args = cli::parse_export_args(&args[2..]).unwrap_or_else(|e| {
+            eprintln!("{e}\n\n{}", cli::EXPORT_USAGE);
+            process::exit(1);
+        });
+        if let Err(e) = cli::export(export_args) {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+        return Ok(());
+    }
+    if args.len() > 1 && args[1] == "diff" {
+        let diff_args = cli::parse_diff_args(&args[2..]).unwrap_or_else(|e| {
+            eprintln!("{e}\n\n{}", cli::DIFF_USAGE);
+            process::exit(2);
+        });
+        match cli::diff(diff_args) {
+            Ok(true) => return Ok(()),
+            Ok(false) => process::exit(1),
+            Err(e) => {
+                eprintln!("{e}");
+                process::exit(2);
+            }
+        }
+    }
 
-    let svg_content = replace_fonts(svg_content);
-    let layers = get_layers(&svg_content);
+    let mut flags = PictureFlags::default();
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--preset" {
+            let Some(preset) = args.next() else {
+                eprintln!("missing value for {arg}");
+                process::exit(1);
+            };
+            flags.preset = Some(preset.clone());
+            continue;
+        }
+        match Document::open(arg) {
+            Ok(document) => flags.documents.push(document),
+            // still opens the other files, showing the first error
+            Err(e) => {
+                flags.error.get_or_insert(e);
+            }
+        }
+    }
 
-    Picture::run(Settings::with_flags(PictureFlags {
-        svg_content,
-        file_name,
-        layers,
-    }))
+    Picture::run(Settings::with_flags(flags))
 }
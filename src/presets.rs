@@ -0,0 +1,210 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{config_dir, read_optional};
+use crate::error::Error;
+use crate::layers::{Layer, LayerKey};
+
+/// A named set of layer visibilities, such as "print" or "answers hidden".
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Preset {
+    pub(crate) name: String,
+    pub(crate) layers: Vec<(LayerKey, bool)>,
+}
+
+impl Preset {
+    /// Records the visibility of every layer.
+    pub(crate) fn new(name: String, layers: &[Layer]) -> Self {
+        Preset {
+            name,
+            layers: layers.iter().map(|l| (l.key.clone(), l.visible)).collect(),
+        }
+    }
+
+    /// Shows and hides the layers as recorded, leaving the layers added since
+    /// untouched.
+    pub(crate) fn apply(&self, layers: &mut [Layer]) {
+        for layer in layers {
+            if let Some((_, visible)) = self.layers.iter().find(|(key, _)| *key == layer.key) {
+                layer.visible = *visible;
+            }
+        }
+    }
+}
+
+/// Loads the presets of a document, from the sidecar file next to it or, when
+/// there is none, from the configuration directory.
+pub(crate) fn load_presets(file_name: &str) -> Result<Vec<Preset>, Error> {
+    for path in [Some(sidecar_path(file_name)), config_path(file_name)]
+        .into_iter()
+        .flatten()
+    {
+        if let Some(content) = read_optional(&path)? {
+            return parse_presets(&path, &content);
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// Saves the presets of a document next to it, or in the configuration
+/// directory when they are already there or the directory of the document is
+/// not writable.
+pub(crate) fn save_presets(file_name: &str, presets: &[Preset]) -> Result<(), Error> {
+    let content = write_presets(presets);
+    let sidecar = sidecar_path(file_name);
+    let config = config_path(file_name);
+    if let Some(config) = config.as_ref().filter(|c| c.exists() && !sidecar.exists()) {
+        return write(config, &content);
+    }
+    match (write(&sidecar, &content), config) {
+        (Err(_), Some(config)) => {
+            if let Some(dir) = config.parent() {
+                fs::create_dir_all(dir).map_err(|e| Error::io(&dir.to_string_lossy(), e))?;
+            }
+            write(&config, &content)
+        }
+        (written, _) => written,
+    }
+}
+
+// drawing.svg -> drawing.svg.presets
+fn sidecar_path(file_name: &str) -> PathBuf {
+    PathBuf::from(format!("{file_name}.presets"))
+}
+
+// the whole path of the document, with `%` for separators, as Vim does for its
+// undo files
+fn config_path(file_name: &str) -> Option<PathBuf> {
+    let name = file_name.replace(['/', '\\'], "%");
+    Some(
+        config_dir()?
+            .join("presets")
+            .join(format!("{name}.presets")),
+    )
+}
+
+fn write(path: &Path, content: &str) -> Result<(), Error> {
+    fs::write(path, content).map_err(|e| Error::io(&path.to_string_lossy(), e))
+}
+
+/// Parses presets written as sections of layers, by id or by position, `@`
+/// being invalid in XML ids, with `#` comments as in `keys.conf`:
+///
+/// ```text
+/// # for the printer
+/// [print]
+/// layer1 = shown
+/// @3 = hidden
+/// ```
+fn parse_presets(path: &Path, content: &str) -> Result<Vec<Preset>, Error> {
+    let mut presets: Vec<Preset> = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || Error::Config(path.to_string_lossy().into(), number + 1);
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            presets.push(Preset {
+                name: name.trim().to_string(),
+                layers: Vec::new(),
+            });
+            continue;
+        }
+        let (key, visibility) = line.rsplit_once('=').ok_or_else(invalid)?;
+        let key = match key.trim().strip_prefix('@') {
+            Some(position) => LayerKey::Position(position.parse().map_err(|_| invalid())?),
+            None => LayerKey::Id(key.trim().to_string()),
+        };
+        let visible = match visibility.trim() {
+            "shown" => true,
+            "hidden" => false,
+            _ => return Err(invalid()),
+        };
+        presets
+            .last_mut()
+            .ok_or_else(invalid)?
+            .layers
+            .push((key, visible));
+    }
+    Ok(presets)
+}
+
+fn write_presets(presets: &[Preset]) -> String {
+    let mut content = String::new();
+    for preset in presets {
+        content.push_str(&format!("[{}]\n", preset.name));
+        for (key, visible) in &preset.layers {
+            let key = match key {
+                LayerKey::Id(id) => id.clone(),
+                LayerKey::Position(position) => format!("@{position}"),
+            };
+            let visibility = if *visible { "shown" } else { "hidden" };
+            content.push_str(&format!("{key} = {visibility}\n"));
+        }
+        content.push('\n');
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_layers_by_id_and_position() {
+        let content = "# for the printer\n[print]\nbase = shown\n @2 = hidden\n\n[empty]\n";
+        let presets = parse_presets(Path::new("a.svg.presets"), content).unwrap();
+        assert_eq!(presets.len(), 2);
+        assert_eq!(presets[0].name, "print");
+        assert_eq!(
+            presets[0].layers,
+            vec![
+                (LayerKey::Id("base".to_string()), true),
+                (LayerKey::Position(2), false)
+            ]
+        );
+        assert!(presets[1].layers.is_empty());
+    }
+
+    #[test]
+    fn tells_comments_from_positions() {
+        let path = Path::new("a.svg.presets");
+        let presets = parse_presets(path, "[p]\n#1 = hidden\n@1 = shown\n").unwrap();
+        assert_eq!(presets[0].layers, vec![(LayerKey::Position(1), true)]);
+    }
+
+    #[test]
+    fn reports_the_invalid_line() {
+        let path = Path::new("a.svg.presets");
+        let error = parse_presets(path, "[print]\nbase = maybe\n").unwrap_err();
+        assert!(matches!(error, Error::Config(_, 2)));
+        // a layer outside of any preset
+        let error = parse_presets(path, "base = shown\n").unwrap_err();
+        assert!(matches!(error, Error::Config(_, 1)));
+        let error = parse_presets(path, "[print]\n@two = shown\n").unwrap_err();
+        assert!(matches!(error, Error::Config(_, 2)));
+    }
+
+    #[test]
+    fn reads_what_it_writes() {
+        let presets = vec![
+            Preset {
+                name: "print".to_string(),
+                layers: vec![
+                    (LayerKey::Id("base".to_string()), true),
+                    (LayerKey::Position(3), false),
+                ],
+            },
+            Preset {
+                name: "answers hidden".to_string(),
+                layers: vec![(LayerKey::Id("answers".to_string()), false)],
+            },
+        ];
+        let content = write_presets(&presets);
+        assert_eq!(
+            parse_presets(Path::new("a.svg.presets"), &content).unwrap(),
+            presets
+        );
+    }
+}
@@ -1,56 +1,81 @@
-use std::borrow::Cow;
-use std::io::Cursor;
-
-use quick_xml::events::attributes::Attribute;
-use quick_xml::events::{BytesStart, Event};
-use quick_xml::{Reader, Writer};
-
-pub(crate) fn transform_svg(
-    svg_content: &[u8],
-    matrix_transform: (f32, f32, f32, f32, f32, f32),
-) -> Vec<u8> {
-    let mut reader = Reader::from_reader(svg_content);
-    reader.config_mut().trim_text(true);
-    let mut writer = Writer::new(Cursor::new(Vec::new()));
-    loop {
-        match reader.read_event() {
-            Ok(Event::Start(element)) if element.name().as_ref() == b"svg" => {
-                let mut new_svg = BytesStart::new("svg");
-
-                let has_transform = element.attributes().any(|a| {
-                    let attr = a.as_ref().unwrap();
-                    attr.key.as_ref() == b"transform"
-                });
-
-                let (a, b, c, d, e, f) = matrix_transform;
-                let attr_transform = format!("matrix({a} {b} {c} {d} {e} {f})");
-
-                let current_attributes = element.attributes();
-                if !has_transform {
-                    new_svg.extend_attributes(current_attributes.map(|a| a.unwrap()));
-                    new_svg.push_attribute(("transform", attr_transform.as_ref()));
-                } else {
-                    new_svg.extend_attributes(current_attributes.map(|a| {
-                        let attr = a.unwrap();
-                        if attr.key.as_ref() == b"transform" {
-                            Attribute {
-                                key: attr.key,
-                                value: Cow::from(attr_transform.as_bytes()),
-                            }
-                        } else {
-                            attr
-                        }
-                    }));
-                }
-                writer.write_event(Event::Start(new_svg)).unwrap()
-            }
-            Ok(Event::Eof) => break,
-            Ok(e) => {
-                writer.write_event(e).unwrap();
-            }
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
-        }
-    }
-
-    writer.into_inner().into_inner()
+use resvg::tiny_skia::{Point, Transform};
+use resvg::usvg::Size;
+
+/// A zoom/pan matrix, as in the SVG `matrix(a b c d e f)` transform.
+pub(crate) type Matrix = (f32, f32, f32, f32, f32, f32);
+
+pub(crate) const MIN_ZOOM: f32 = 0.01;
+pub(crate) const MAX_ZOOM: f32 = 1000.0;
+
+/// Scale and offsets fitting a document in the bounds, centered, as shown with
+/// no zoom/pan.
+pub(crate) fn fit(document_size: Size, bounds: (f32, f32)) -> (f32, f32, f32) {
+    let (width, height) = bounds;
+    let fit = (width / document_size.width()).min(height / document_size.height());
+    let offset_x = (width - document_size.width() * fit) / 2.0;
+    let offset_y = (height - document_size.height() * fit) / 2.0;
+    (fit, offset_x, offset_y)
+}
+
+/// Maps document units to widget pixels: the document is scaled to fit the
+/// bounds and centered, then the zoom/pan matrix is applied in document units.
+pub(crate) fn view_transform(
+    matrix_transform: Matrix,
+    document_size: Size,
+    bounds: (f32, f32),
+) -> Transform {
+    let (fit, offset_x, offset_y) = fit(document_size, bounds);
+    let (a, b, c, d, e, f) = matrix_transform;
+    Transform::from_row(fit, 0.0, 0.0, fit, offset_x, offset_y)
+        .pre_concat(Transform::from_row(a, b, c, d, e, f))
+}
+
+/// Maps widget pixels to document units, as the zoom/pan matrix sees them,
+/// undoing the fit of the document in the bounds.
+pub(crate) fn to_document_units(
+    document_size: Size,
+    bounds: (f32, f32),
+    (x, y): (f32, f32),
+) -> (f32, f32) {
+    let (fit, offset_x, offset_y) = fit(document_size, bounds);
+    ((x - offset_x) / fit, (y - offset_y) / fit)
+}
+
+/// Zoom/pan matrix filling the bounds with the width of the document, showing
+/// its top.
+pub(crate) fn fit_width(document_size: Size, bounds: (f32, f32)) -> Matrix {
+    let (fit, offset_x, offset_y) = fit(document_size, bounds);
+    let zoom = bounds.0 / (document_size.width() * fit);
+    (zoom, 0.0, 0.0, zoom, -offset_x / fit, -offset_y / fit)
+}
+
+/// Zoom/pan matrix showing one document unit per pixel, keeping the document
+/// centered.
+pub(crate) fn actual_size(document_size: Size, bounds: (f32, f32)) -> Matrix {
+    let (fit, _, _) = fit(document_size, bounds);
+    let zoom = 1.0 / fit;
+    (
+        zoom,
+        0.0,
+        0.0,
+        zoom,
+        (1.0 - zoom) * document_size.width() / 2.0,
+        (1.0 - zoom) * document_size.height() / 2.0,
+    )
+}
+
+/// Maps a point of the view, in document units, back to the user units of the
+/// document, undoing the zoom/pan and the `viewBox` of the root.
+pub(crate) fn to_user_units(
+    matrix_transform: Matrix,
+    root_transform: Transform,
+    (x, y): (f32, f32),
+) -> Option<(f32, f32)> {
+    let (a, b, c, d, e, f) = matrix_transform;
+    let inverse = Transform::from_row(a, b, c, d, e, f)
+        .pre_concat(root_transform)
+        .invert()?;
+    let mut point = Point::from_xy(x, y);
+    inverse.map_point(&mut point);
+    Some((point.x, point.y))
 }
@@ -1,99 +1,535 @@
 use core::f32;
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs};
 
-use crate::export::{export_png, generate_png, generate_svg_tree};
+use crate::batch::{export_batch, Batch, DEFAULT_TEMPLATE};
+use crate::document::Document;
+use crate::error::Error;
+use crate::export::{export_png, export_svg, generate_png};
 use crate::ext_svg::ExtendedSvg;
-use crate::layers::set_visible_layers;
-use crate::transform::transform_svg;
+use crate::keys::{default_bindings, load_bindings, Action, Bindings};
+use crate::layers::{remove_hidden_layers, set_visible_layers, solo_layer, Layer};
+use crate::presets::{load_presets, save_presets, Preset};
+use crate::transform::{
+    actual_size, fit_width, to_document_units, to_user_units, Matrix, MAX_ZOOM, MIN_ZOOM,
+};
+use iced::keyboard::{self, Key, Modifiers};
 use iced::mouse::{
+    self,
     Button::Left,
     Event::{ButtonPressed, ButtonReleased, CursorMoved, WheelScrolled},
     ScrollDelta,
 };
 use iced::widget::{
-    button, checkbox, column, container, row, svg, text, text_input, Checkbox, Container, Row,
+    button, checkbox, column, container, horizontal_space, mouse_area, pick_list, row, scrollable,
+    slider, text, text_input, vertical_rule, Column, Container, Row,
 };
-use iced::Event::{Mouse, Window};
+use iced::Event::{Keyboard, Mouse, Window};
 use iced::{
-    event, executor, font, theme, Application, Command, Element, Length, Point, Subscription,
+    event, executor, font, theme, time, window, Application, Command, Element, Length, Point,
+    Rectangle, Subscription,
 };
-use iced_aw::number_input;
 use iced_aw::widgets::Modal;
-use iced_style::core::window;
-use resvg::usvg::{Size, Tree};
+use iced_aw::{number_input, Bootstrap, BOOTSTRAP_FONT};
+use resvg::usvg::Size;
+
+// zoom factor of the zoom in/out shortcuts
+const ZOOM_STEP: f32 = 1.25;
+// pixels moved by the pan shortcuts
+const PAN_STEP: f32 = 50.0;
+// pixels scrolled by one line of a mouse wheel, as in iced scrollables
+const LINE_HEIGHT: f32 = 60.0;
+// width of the layer panel, resizable by dragging its edge
+const PANEL_WIDTH: f32 = 260.0;
+const MIN_PANEL_WIDTH: f32 = 220.0;
+// room left to the document when widening the panel
+const MIN_VIEW_WIDTH: f32 = 100.0;
 
 #[derive(Debug, Default)]
 pub(crate) struct Picture {
     // graphical properties
     ask_overwrite: bool,
     show_modal: bool,
+    show_svg_modal: bool,
+    show_open_modal: bool,
     panning: bool,
-    current_scroll: f32,
+    resizing: bool,
+    // cursor position, in the window and in document units
+    cursor: Point,
     current_x: f32,
     current_y: f32,
-    current_width: u32,
-    current_height: u32,
+    view_bounds: Rectangle,
+    // bounds of the compared document, and whether the cursor is over it
+    compare_bounds: Rectangle,
+    compare_side: bool,
+    bindings: Bindings,
+    modifiers: Modifiers,
+    // physical pixels per logical pixel, measured from a screenshot whenever
+    // the window is resized, as iced does not report it
+    scale_factor: f32,
+    window_width: f32,
+    measuring_scale: bool,
+
+    // open documents, one per tab, each with its own layers and zoom/pan
+    documents: Vec<Document>,
+    active: usize,
+    // document shown next to the active one, sharing its zoom/pan
+    compare: Option<usize>,
+    // shows the pixel difference from the compared document instead
+    show_diff: bool,
+
+    // name of the preset to save
+    preset_name: String,
+
+    // layer panel
+    panel_width: f32,
+    layer_filter: String,
 
-    // content + layers
-    file_name: String,
-    svg_content: Vec<u8>,
-    layers: Vec<(String, bool)>,
-    matrix_transform: (f32, f32, f32, f32, f32, f32),
+    // file picker
+    open_dir: PathBuf,
+    open_entries: Vec<PathBuf>,
+    open_path: String,
 
-    // PNG + structure
+    // error to report, replacing the view if no document is loaded
+    error: Option<Error>,
+
+    // PNG/SVG output
     png_content: Vec<u8>,
-    svg_tree: Option<Tree>,
-    height: f32,
-    width: f32,
-    ratio: f32,
+    svg_output: Vec<u8>,
 
-    // output properties
-    output_file_name: String,
+    // output properties, the file names are kept by each document
     output_width: f32,
     output_height: f32,
+    batch: Batch,
+    output_template: String,
+    strip_hidden: bool,
+}
+
+/// A tab, as listed by the comparison picker.
+#[derive(Debug, Clone, PartialEq)]
+struct Tab {
+    index: usize,
+    title: String,
+}
+
+impl fmt::Display for Tab {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.title)
+    }
 }
 
 #[derive(Debug, Default)]
 pub(crate) struct PictureFlags {
-    pub(crate) file_name: String,
-    pub(crate) svg_content: Vec<u8>,
-    pub(crate) layers: Vec<String>,
+    pub(crate) documents: Vec<Document>,
+    // applied to every document having it
+    pub(crate) preset: Option<String>,
+    pub(crate) error: Option<Error>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    // layers
-    ToggleLayerVisibility(String, bool),
+    // tabs
+    SelectTab(usize),
+    CloseTab(usize),
+    DuplicateTab,
+    Compare(usize),
+    StopCompare,
+    ShowDiff(bool),
+    // presets of the active document
+    ApplyPreset(String),
+    PresetName(String),
+    SavePreset,
+    // layers, by document and layer index
+    ToggleLayerVisibility(usize, usize, bool),
+    ToggleLayerExpanded(usize, usize),
+    LayerOpacity(usize, usize, f32),
+    ApplyLayers(usize),
+    SoloLayer(usize, usize),
+    ShowAllLayers(usize),
+    HideAllLayers(usize),
+    InvertLayers(usize),
+    LayerFilter(String),
     // output information
     OutFileName(String),
     OutWidth(f32),
     OutHeight(f32),
+    OutBatch(Batch),
+    OutTemplate(String),
     // export + overwrite modals
     OpenExport,
     SaveExport,
     CancelExport,
     Overwrite,
     NoOverwrite,
+    // save SVG modal
+    OutSvgFileName(String),
+    StripHidden(bool),
+    OpenSaveSvg,
+    SaveSvg,
+    CancelSaveSvg,
+    // open modal
+    OpenFile,
+    Browse(PathBuf),
+    OpenPath(String),
+    ConfirmOpen,
+    CancelOpen,
+    // error dialog
+    DismissError,
+    // live reload
+    CheckModified,
     // preload
     FontLoaded,
+    // logical and physical width of the window, telling the scale factor
+    WindowResized(f32),
+    PhysicalWidth(u32),
     // events
-    Scroll(f32),
+    Scroll(ScrollDelta),
     StartPan,
+    StartResize,
     CursorMoved(f32, f32),
     EndPan,
+    // fits the whole document in the view, without pan
     Reset,
-    Resized(u32, u32),
+    FitWidth,
+    ActualSize,
+    ZoomTo(f32),
+    ViewBounds(Rectangle),
+    CompareBounds(Rectangle),
+    KeyPressed(Key, Modifiers),
+    ModifiersChanged(Modifiers),
 }
 
 impl Picture {
-    fn save_modal(&self) -> Container<Message> {
-        container(
-            column![column![
+    /// Keeps the error to show it to the user, discarding the result.
+    fn report<T>(&mut self, result: Result<T, Error>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    fn document(&self) -> Option<&Document> {
+        self.documents.get(self.active)
+    }
+
+    fn document_mut(&mut self) -> Option<&mut Document> {
+        self.documents.get_mut(self.active)
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        (self.view_bounds.width, self.view_bounds.height)
+    }
+
+    /// The bounds of the view in physical pixels, those of the screen.
+    fn physical_bounds(&self) -> (f32, f32) {
+        let (width, height) = self.bounds();
+        (width * self.scale_factor, height * self.scale_factor)
+    }
+
+    /// The document under the cursor, the compared one or the active one, with
+    /// the bounds of its view.
+    fn hovered(&self) -> (usize, Rectangle) {
+        match self.compare {
+            Some(compared) if self.compare_side => (compared, self.compare_bounds),
+            _ => (self.active, self.view_bounds),
+        }
+    }
+
+    /// Whether the cursor is over a document, rather than over the layers or
+    /// the controls.
+    fn over_view(&self) -> bool {
+        let (_, bounds) = self.hovered();
+        bounds.contains(self.cursor)
+    }
+
+    /// Gives a document the zoom/pan of another one.
+    fn copy_matrix(&mut self, from: usize, to: usize) {
+        let (matrix_transform, current_scroll) = {
+            let document = &self.documents[from];
+            (document.matrix_transform, document.current_scroll)
+        };
+        let document = &mut self.documents[to];
+        document.matrix_transform = matrix_transform;
+        document.current_scroll = current_scroll;
+    }
+
+    /// Shows the document in a new tab, or the tab already showing it.
+    fn add_document(&mut self, mut document: Document) {
+        let presets = load_presets(&document.file_name);
+        document.presets = self.report(presets).unwrap_or_default();
+        match self
+            .documents
+            .iter()
+            .position(|d| d.file_name == document.file_name)
+        {
+            Some(index) => self.active = index,
+            None => {
+                self.documents.push(document);
+                self.active = self.documents.len() - 1;
+            }
+        }
+    }
+
+    fn run_action(&mut self, action: Action) -> Command<Message> {
+        let (bounds, active) = (self.bounds(), self.active);
+        let Some(document) = self.document_mut() else {
+            return match action {
+                Action::Open => self.update(Message::OpenFile),
+                _ => Command::none(),
+            };
+        };
+        let center = (document.width / 2.0, document.height / 2.0);
+        // pan by a fixed amount of pixels, whatever the zoom
+        let step = match document.fit_scale(bounds) {
+            fit if fit > 0.0 => PAN_STEP / fit,
+            _ => 0.0,
+        };
+        match action {
+            Action::ZoomIn => document.zoom_around(document.current_scroll * ZOOM_STEP, center),
+            Action::ZoomOut => document.zoom_around(document.current_scroll / ZOOM_STEP, center),
+            Action::PanLeft => document.pan(step, 0.0),
+            Action::PanRight => document.pan(-step, 0.0),
+            Action::PanUp => document.pan(0.0, step),
+            Action::PanDown => document.pan(0.0, -step),
+            Action::Reset => return self.update(Message::Reset),
+            Action::Export => return self.update(Message::OpenExport),
+            Action::Open => return self.update(Message::OpenFile),
+            Action::Reload => {
+                let reloaded = document.reload();
+                self.report(reloaded);
+            }
+            Action::ToggleLayer(layer) => {
+                if let Some(visible) = document.layers.get(layer).map(|l| l.visible) {
+                    let message = Message::ToggleLayerVisibility(active, layer, !visible);
+                    return self.update(message);
+                }
+            }
+        }
+        Command::none()
+    }
+
+    /// Changes the layers of a document, then renders it once for all the
+    /// changes.
+    fn update_layers(&mut self, document: usize, change: impl FnOnce(&mut [Layer])) {
+        if let Some(document) = self.documents.get_mut(document) {
+            change(&mut document.layers);
+            let applied = document.apply_layers();
+            self.report(applied);
+        }
+    }
+
+    fn tab_bar(&self) -> Row<'_, Message> {
+        let mut tabs = Row::new().spacing(10).width(Length::Fill);
+        for (index, document) in self.documents.iter().enumerate() {
+            let style = if index == self.active {
+                theme::Button::Primary
+            } else {
+                theme::Button::Secondary
+            };
+            let close_button = button(text(char::from(Bootstrap::X)).font(BOOTSTRAP_FONT).size(16))
+                .on_press(Message::CloseTab(index))
+                .padding(0)
+                .style(theme::Button::Text);
+            tabs = tabs.push(
+                row![
+                    button(text(document.title()).size(16))
+                        .on_press(Message::SelectTab(index))
+                        .style(style),
+                    close_button
+                ]
+                .spacing(5)
+                .align_items(iced::Alignment::Center),
+            );
+        }
+
+        let duplicate_button = button(text("Duplicate").size(16)).on_press(Message::DuplicateTab);
+        let others: Vec<Tab> = self
+            .documents
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != self.active)
+            .map(|(index, document)| Tab {
+                index,
+                title: document.title(),
+            })
+            .collect();
+        let compared = self
+            .compare
+            .and_then(|compared| others.iter().find(|tab| tab.index == compared).cloned());
+        let compare_list = pick_list(others, compared, |tab| Message::Compare(tab.index))
+            .placeholder("Compare with…")
+            .text_size(16);
+        tabs = tabs
+            .push(horizontal_space())
+            .push(duplicate_button)
+            .push(compare_list);
+        if self.compare.is_some() {
+            let diff_checkbox = checkbox("Difference", self.show_diff)
+                .on_toggle(Message::ShowDiff)
+                .size(16);
+            let stop_button =
+                button(text("Stop comparing").size(16)).on_press(Message::StopCompare);
+            tabs = tabs.push(diff_checkbox).push(stop_button);
+        }
+        tabs
+    }
+
+    /// The view of a document with its layers, drawn with the given zoom/pan,
+    /// or as its difference from another document.
+    fn document_view(
+        &self,
+        index: usize,
+        before: Option<usize>,
+        matrix_transform: Matrix,
+        on_bounds: fn(Rectangle) -> Message,
+    ) -> Row<'_, Message> {
+        let document = &self.documents[index];
+        let mut svg = ExtendedSvg::new(document.svg_tree.clone(), matrix_transform)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .scale_factor(self.scale_factor)
+            .on_bounds(on_bounds);
+        if let Some(before) = before {
+            svg = svg.diff(self.documents[before].svg_tree.clone());
+        }
+
+        // Alt+click shows a layer alone
+        let solo = self.modifiers.alt();
+        let filter = self.layer_filter.to_lowercase();
+        let mut trees = Column::new().spacing(5).width(Length::Fill);
+        for (i, _) in
+            document.layers.iter().enumerate().filter(|(i, l)| {
+                l.parent.is_none() && matches_filter(&document.layers, *i, &filter)
+            })
+        {
+            trees = trees.push(layer_tree(index, &document.layers, i, solo, &filter))
+        }
+
+        let total = document.layers.len();
+        let shown = document.layers.iter().filter(|l| l.visible).count();
+        let count = if filter.is_empty() {
+            format!("{total} layers, {shown} shown")
+        } else {
+            let matching = (0..total)
+                .filter(|i| document.layers[*i].name.to_lowercase().contains(&filter))
+                .count();
+            format!("{matching} of {total} layers match, {shown} shown")
+        };
+        let actions = row![
+            button(text("Show all").size(14)).on_press(Message::ShowAllLayers(index)),
+            button(text("Hide all").size(14)).on_press(Message::HideAllLayers(index)),
+            button(text("Invert").size(14)).on_press(Message::InvertLayers(index)),
+        ]
+        .spacing(10);
+        let panel = column![
+            text_input("Filter layers", &self.layer_filter)
+                .on_input(Message::LayerFilter)
+                .size(14),
+            text(count).size(14),
+            actions,
+            scrollable(trees).height(Length::Fill),
+        ]
+        .spacing(10)
+        .width(Length::Fixed(self.panel_width))
+        .height(Length::Fill);
+
+        // dragging the rule resizes the panel
+        let edge = mouse_area(
+            container(vertical_rule(1))
+                .width(Length::Fixed(9.0))
+                .height(Length::Fill)
+                .center_x(),
+        )
+        .on_press(Message::StartResize)
+        .interaction(mouse::Interaction::ResizingHorizontally);
+
+        row![svg, edge, panel]
+            .spacing(5)
+            .width(Length::Fill)
+            .height(Length::Fill)
+    }
+
+    fn status_bar(&self, document: &Document) -> Row<'_, Message> {
+        // 100% is one screen pixel per document unit, as with "Actual size"
+        let fit = document.fit_scale(self.physical_bounds());
+        // as far as the zoom goes, fitting large drawings taking less than 1%
+        let (min, max) = (MIN_ZOOM * fit * 100.0, MAX_ZOOM * fit * 100.0);
+        let percentage = ((document.current_scroll * fit * 10_000.0).round() / 100.0).max(min);
+        let (hovered, _) = self.hovered();
+        let position = to_user_units(
+            document.matrix_transform,
+            self.documents[hovered].svg_tree.root().transform(),
+            (self.current_x, self.current_y),
+        )
+        .map(|(x, y)| format!("x: {x:.1}  y: {y:.1}"))
+        .unwrap_or_default();
+
+        row![
+            text("Zoom").size(14),
+            number_input(percentage, max, Message::ZoomTo)
+                .min(min)
+                .step(10.0)
+                .size(14.0),
+            text("%").size(14),
+            horizontal_space(),
+            text(position).size(14),
+        ]
+        .spacing(10)
+        .align_items(iced::Alignment::Center)
+        .width(Length::Fill)
+    }
+
+    fn browse(&mut self, dir: PathBuf) {
+        let entries = fs::read_dir(&dir).map_err(|e| Error::io(&dir.to_string_lossy(), e));
+        let Some(entries) = self.report(entries) else {
+            return;
+        };
+        let mut entries: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                let hidden = path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                let svg = path.extension().is_some_and(|ext| ext == "svg");
+                !hidden && (path.is_dir() || svg)
+            })
+            .collect();
+        // directories first, then files, both by name
+        entries.sort_by_key(|path| (!path.is_dir(), path.file_name().map(|n| n.to_owned())));
+        self.open_dir = dir;
+        self.open_entries = entries;
+    }
+
+    fn save_modal(&self) -> Container<'_, Message> {
+        let path = if self.batch == Batch::Current {
+            let output_file_name = self.document().map_or("", |d| &d.output_file_name);
+            column![
                 text("Path").size(16),
-                text_input("", &self.output_file_name)
+                text_input("", output_file_name)
                     .on_input(Message::OutFileName)
                     .width(Length::Fixed(500.0))
                     .size(16),
+            ]
+        } else {
+            column![
+                text("Names, from {stem}, {layer} and {index}").size(16),
+                text_input(DEFAULT_TEMPLATE, &self.output_template)
+                    .on_input(Message::OutTemplate)
+                    .width(Length::Fixed(500.0))
+                    .size(16),
+            ]
+        };
+        container(
+            column![column![
+                text("Layers").size(16),
+                pick_list(Batch::ALL, Some(self.batch), Message::OutBatch).text_size(16),
+                path.spacing(10),
                 text("Width").size(16),
                 number_input(self.output_width, f32::MAX, Message::OutWidth).size(16.0),
                 text("Height").size(16),
@@ -114,7 +550,109 @@ impl Picture {
         .style(theme::Container::Box)
     }
 
-    fn overwrite_modal(&self) -> Container<Message> {
+    fn save_svg_modal(&self) -> Container<'_, Message> {
+        let output_svg_file_name = self.document().map_or("", |d| &d.output_svg_file_name);
+        container(
+            column![column![
+                text("Path").size(16),
+                text_input("", output_svg_file_name)
+                    .on_input(Message::OutSvgFileName)
+                    .width(Length::Fixed(500.0))
+                    .size(16),
+                checkbox("Remove hidden layers", self.strip_hidden)
+                    .on_toggle(Message::StripHidden)
+                    .size(16),
+                container(
+                    row![
+                        button(text("Cancel").size(16)).on_press(Message::CancelSaveSvg),
+                        button(text("Save").size(16)).on_press(Message::SaveSvg),
+                    ]
+                    .spacing(20)
+                )
+                .center_x()
+            ]
+            .spacing(10)]
+            .spacing(20),
+        )
+        .padding(15)
+        .style(theme::Container::Box)
+    }
+
+    fn open_modal(&self) -> Container<'_, Message> {
+        let mut entries = Column::new().spacing(2).width(Length::Fill);
+        if let Some(parent) = self.open_dir.parent() {
+            entries = entries.push(
+                button(text("..").size(16))
+                    .on_press(Message::Browse(parent.to_path_buf()))
+                    .style(theme::Button::Text)
+                    .width(Length::Fill),
+            );
+        }
+        for entry in &self.open_entries {
+            let mut name = entry
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            if entry.is_dir() {
+                name.push('/');
+            }
+            entries = entries.push(
+                button(text(name).size(16))
+                    .on_press(Message::Browse(entry.clone()))
+                    .style(theme::Button::Text)
+                    .width(Length::Fill),
+            );
+        }
+
+        container(
+            column![column![
+                text(self.open_dir.to_string_lossy()).size(16),
+                container(scrollable(entries))
+                    .height(Length::Fixed(300.0))
+                    .style(theme::Container::Box),
+                text("Path").size(16),
+                text_input("", &self.open_path)
+                    .on_input(Message::OpenPath)
+                    .on_submit(Message::ConfirmOpen)
+                    .size(16),
+                container(
+                    row![
+                        button(text("Cancel").size(16)).on_press(Message::CancelOpen),
+                        button(text("Open").size(16)).on_press(Message::ConfirmOpen),
+                    ]
+                    .spacing(20)
+                )
+                .center_x()
+            ]
+            .spacing(10)]
+            .spacing(20),
+        )
+        .width(Length::Fixed(530.0))
+        .padding(15)
+        .style(theme::Container::Box)
+    }
+
+    fn error_modal(&self) -> Container<'_, Message> {
+        container(
+            column![
+                text(
+                    self.error
+                        .as_ref()
+                        .map(Error::to_string)
+                        .unwrap_or_default()
+                )
+                .size(16),
+                container(button(text("OK").size(16)).on_press(Message::DismissError)).center_x()
+            ]
+            .spacing(20),
+        )
+        .width(Length::Shrink)
+        .padding(15)
+        .style(theme::Container::Box)
+    }
+
+    fn overwrite_modal(&self) -> Container<'_, Message> {
         container(
             column![column![
                 column![text("File exists. Overwrite?").size(16),].spacing(5),
@@ -143,236 +681,693 @@ impl Application for Picture {
     type Executor = executor::Default;
 
     fn new(flags: PictureFlags) -> (Self, Command<Message>) {
-        let mut png_path = PathBuf::from(&flags.file_name);
-        png_path.set_extension("png");
-
-        let (svg_tree, pixmap_size) = generate_svg_tree(&flags.svg_content);
-        let height = pixmap_size.height();
-        let width = pixmap_size.width();
-        let ratio = width / height;
-        let output_height = pixmap_size.height();
-        let output_width = pixmap_size.width();
-
-        let picture = Picture {
-            ask_overwrite: false,
-            show_modal: false,
-            svg_content: flags.svg_content,
-            file_name: flags.file_name,
-            output_file_name: String::from(png_path.to_str().unwrap()),
-            layers: flags.layers.iter().map(|l| (l.to_string(), true)).collect(),
-            matrix_transform: (1.0, 0.0, 0.0, 1.0, 0.0, 0.0),
-            svg_tree: Some(svg_tree),
-            ratio,
-            height,
-            width,
-            output_height,
-            output_width,
-            current_scroll: 1.0,
+        let mut picture = Picture {
+            output_template: DEFAULT_TEMPLATE.to_string(),
+            panel_width: PANEL_WIDTH,
+            scale_factor: 1.0,
             ..Default::default()
         };
+        let bindings = load_bindings();
+        picture.bindings = picture.report(bindings).unwrap_or_else(default_bindings);
+        for document in flags.documents {
+            picture.add_document(document);
+        }
+        if let Some(name) = flags.preset {
+            let mut applied = Err(Error::UnknownPreset(name.clone()));
+            for document in &mut picture.documents {
+                if document.presets.iter().any(|p| p.name == name) {
+                    applied = document.apply_preset(&name);
+                }
+            }
+            picture.report(applied);
+        }
+        // the first document is shown first
+        picture.active = 0;
+        if let Some(e) = flags.error {
+            picture.error = Some(e);
+        }
         (
             picture,
-            font::load(iced_aw::BOOTSTRAP_FONT_BYTES).map(|_| Message::FontLoaded),
+            Command::batch([
+                font::load(iced_aw::BOOTSTRAP_FONT_BYTES).map(|_| Message::FontLoaded),
+                window::fetch_size(window::Id::MAIN, |size| Message::WindowResized(size.width)),
+            ]),
         )
     }
 
     fn title(&self) -> String {
-        self.file_name.clone()
+        match self.document() {
+            Some(document) => document.file_name.clone(),
+            None => String::from("viewrs"),
+        }
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        event::listen_with(|event, status| {
+        let events = event::listen_with(|event, status| {
+            // needed whatever the focus, to tell zooming from panning
+            if let Keyboard(keyboard::Event::ModifiersChanged(modifiers)) = event {
+                return Some(Message::ModifiersChanged(modifiers));
+            }
+            // drags go on outside of the widget where they started
+            match event {
+                Window(_, window::Event::Resized { width, .. }) => {
+                    return Some(Message::WindowResized(width as f32))
+                }
+                Mouse(ButtonReleased(Left)) => return Some(Message::EndPan),
+                Mouse(CursorMoved {
+                    position: Point { x, y },
+                }) => return Some(Message::CursorMoved(x, y)),
+                _ => (),
+            }
             if status == iced::event::Status::Captured {
                 match event {
                     Mouse(ButtonPressed(Left)) => Some(Message::StartPan),
-                    Mouse(ButtonReleased(Left)) => Some(Message::EndPan),
-                    Mouse(CursorMoved {
-                        position: Point { x, y },
-                    }) => Some(Message::CursorMoved(x, y)),
-                    Mouse(WheelScrolled {
-                        delta: ScrollDelta::Lines { x: _, y },
-                    }) => Some(Message::Scroll(y)),
+                    Mouse(WheelScrolled { delta }) => Some(Message::Scroll(delta)),
                     _ => None,
                 }
             } else {
                 match event {
-                    Window(_, window::Event::Resized { width, height }) => {
-                        Some(Message::Resized(width, height))
+                    // text inputs capture the keys typed in them
+                    Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) => {
+                        Some(Message::KeyPressed(key, modifiers))
                     }
                     _ => None,
                 }
             }
-        })
+        });
+
+        if self.documents.is_empty() {
+            events
+        } else {
+            let file_watch =
+                time::every(Duration::from_millis(500)).map(|_| Message::CheckModified);
+            Subscription::batch([events, file_watch])
+        }
     }
 
     fn update(&mut self, message: Self::Message) -> Command<Message> {
+        let (bounds, physical_bounds) = (self.bounds(), self.physical_bounds());
         match message {
             Message::FontLoaded => Command::none(),
-            Message::ToggleLayerVisibility(layer, visible) => {
-                self.layers = self
-                    .layers
-                    .iter()
-                    .map(|(l, c)| {
-                        let current = l.to_string();
-                        if current == layer {
-                            (current, visible)
-                        } else {
-                            (current, *c)
-                        }
+            Message::WindowResized(width) => {
+                self.window_width = width;
+                if self.measuring_scale {
+                    return Command::none();
+                }
+                self.measuring_scale = true;
+                window::screenshot(window::Id::MAIN, |screenshot| {
+                    Message::PhysicalWidth(screenshot.size.width)
+                })
+            }
+            Message::PhysicalWidth(width) => {
+                self.measuring_scale = false;
+                if self.window_width > 0.0 && width > 0 {
+                    self.scale_factor = width as f32 / self.window_width;
+                }
+                Command::none()
+            }
+            Message::CheckModified => {
+                let mut reloaded = Ok(());
+                for document in &mut self.documents {
+                    let metadata = fs::metadata(&document.file_name).ok();
+                    let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+                    let version = modified.zip(metadata.map(|m| m.len()));
+                    // a missing file is likely being replaced, wait for it to come back
+                    if version.is_none()
+                        || modified == document.modified
+                        || version == document.reload_failed
+                    {
+                        continue;
+                    }
+                    // a file still being written fails to load, and loads once
+                    // written, the reload setting the modification time only
+                    // when it succeeds
+                    if let Err(e) = document.reload() {
+                        document.reload_failed = version;
+                        reloaded = reloaded.and(Err(e));
+                    }
+                }
+                self.report(reloaded);
+                Command::none()
+            }
+            Message::SelectTab(index) => {
+                if let Some(compared) = self.compare {
+                    // keep both documents side by side, only swapping which one is active
+                    if compared == index {
+                        self.compare = Some(self.active);
+                    }
+                    self.copy_matrix(self.active, index);
+                }
+                self.active = index;
+                self.panning = false;
+                Command::none()
+            }
+            Message::CloseTab(index) => {
+                self.documents.remove(index);
+                if self.active > index || self.active == self.documents.len() {
+                    self.active = self.active.saturating_sub(1);
+                }
+                self.compare = match self.compare {
+                    Some(compared) if compared == index => None,
+                    Some(compared) if compared > index => Some(compared - 1),
+                    compared => compared,
+                }
+                .filter(|compared| *compared != self.active);
+                self.panning = false;
+                Command::none()
+            }
+            Message::DuplicateTab => {
+                if let Some(document) = self.document().cloned() {
+                    self.documents.push(document);
+                    self.active = self.documents.len() - 1;
+                }
+                Command::none()
+            }
+            Message::Compare(index) => {
+                if index != self.active && index < self.documents.len() {
+                    self.copy_matrix(self.active, index);
+                    self.compare = Some(index);
+                }
+                Command::none()
+            }
+            Message::StopCompare => {
+                // the compared document keeps the zoom/pan it was shown with
+                if let Some(compared) = self.compare.take() {
+                    self.copy_matrix(self.active, compared);
+                }
+                self.compare_side = false;
+                Command::none()
+            }
+            Message::ShowDiff(show_diff) => {
+                self.show_diff = show_diff;
+                self.compare_side = false;
+                Command::none()
+            }
+            Message::OpenFile => {
+                let dir = self
+                    .document()
+                    .and_then(|document| {
+                        PathBuf::from(&document.file_name)
+                            .parent()
+                            .filter(|dir| !dir.as_os_str().is_empty())
+                            .map(|dir| dir.to_path_buf())
                     })
-                    .collect();
-                self.svg_content = set_visible_layers(&self.svg_content, &self.layers);
+                    .or_else(|| env::current_dir().ok())
+                    .unwrap_or_default();
+                self.browse(dir);
+                self.open_path = String::new();
+                self.show_open_modal = true;
+                Command::none()
+            }
+            Message::Browse(path) => {
+                if path.is_dir() {
+                    self.browse(path);
+                } else {
+                    self.open_path = path.to_string_lossy().to_string();
+                }
+                Command::none()
+            }
+            Message::OpenPath(open_path) => {
+                self.open_path = open_path;
+                Command::none()
+            }
+            Message::ConfirmOpen => {
+                let opened = Document::open(&self.open_path);
+                if let Some(document) = self.report(opened) {
+                    self.add_document(document);
+                    self.show_open_modal = false;
+                    self.error = None;
+                }
+                Command::none()
+            }
+            Message::CancelOpen => {
+                self.show_open_modal = false;
+                Command::none()
+            }
+            Message::DismissError => {
+                self.error = None;
+                Command::none()
+            }
+            Message::ApplyPreset(name) => {
+                if let Some(document) = self.document_mut() {
+                    let applied = document.apply_preset(&name);
+                    self.report(applied);
+                    self.preset_name = name;
+                }
+                Command::none()
+            }
+            Message::PresetName(preset_name) => {
+                self.preset_name = preset_name;
+                Command::none()
+            }
+            Message::SavePreset => {
+                let name = self.preset_name.trim().to_string();
+                let Some(document) = self.documents.get_mut(self.active) else {
+                    return Command::none();
+                };
+                if name.is_empty() {
+                    return Command::none();
+                }
+                // saving under an existing name replaces the preset
+                let preset = Preset::new(name, &document.layers);
+                match document.presets.iter_mut().find(|p| p.name == preset.name) {
+                    Some(existing) => *existing = preset,
+                    None => document.presets.push(preset),
+                }
+                let saved = save_presets(&document.file_name, &document.presets);
+                self.report(saved);
+                Command::none()
+            }
+            Message::ToggleLayerVisibility(document, layer, visible) => {
+                self.update_layers(document, |layers| layers[layer].visible = visible);
+                Command::none()
+            }
+            // the slider only moves while dragged, the document is re-parsed
+            // once released
+            Message::LayerOpacity(document, layer, opacity) => {
+                if let Some(document) = self.documents.get_mut(document) {
+                    document.layers[layer].opacity = opacity;
+                }
+                Command::none()
+            }
+            Message::ApplyLayers(document) => {
+                self.update_layers(document, |_| ());
+                Command::none()
+            }
+            Message::SoloLayer(document, layer) => {
+                self.update_layers(document, |layers| solo_layer(layers, layer));
+                Command::none()
+            }
+            Message::ShowAllLayers(document) => {
+                self.update_layers(document, |layers| {
+                    layers.iter_mut().for_each(|l| l.visible = true)
+                });
+                Command::none()
+            }
+            Message::HideAllLayers(document) => {
+                self.update_layers(document, |layers| {
+                    layers.iter_mut().for_each(|l| l.visible = false)
+                });
+                Command::none()
+            }
+            Message::InvertLayers(document) => {
+                self.update_layers(document, |layers| {
+                    layers.iter_mut().for_each(|l| l.visible = !l.visible)
+                });
+                Command::none()
+            }
+            Message::LayerFilter(layer_filter) => {
+                self.layer_filter = layer_filter;
+                Command::none()
+            }
+            Message::ToggleLayerExpanded(document, layer) => {
+                if let Some(document) = self.documents.get_mut(document) {
+                    document.layers[layer].expanded = !document.layers[layer].expanded;
+                }
                 Command::none()
             }
             Message::OpenExport => {
-                self.show_modal = true;
+                if let Some((width, height)) = self.document().map(|d| (d.width, d.height)) {
+                    self.output_width = width;
+                    self.output_height = height;
+                    self.show_modal = true;
+                }
                 Command::none()
             }
             Message::OutFileName(output_file_name) => {
-                self.output_file_name = output_file_name;
+                if let Some(document) = self.document_mut() {
+                    document.output_file_name = output_file_name;
+                }
+                Command::none()
+            }
+            Message::OutBatch(batch) => {
+                self.batch = batch;
+                Command::none()
+            }
+            Message::OutTemplate(output_template) => {
+                self.output_template = output_template;
                 Command::none()
             }
             Message::OutHeight(height) => {
+                let ratio = self.document().map_or(1.0, Document::ratio);
                 self.output_height = height;
-                self.output_width = height * self.ratio;
+                self.output_width = height * ratio;
                 Command::none()
             }
             Message::OutWidth(width) => {
+                let ratio = self.document().map_or(1.0, Document::ratio);
                 self.output_width = width;
-                self.output_height = width / self.ratio;
+                self.output_height = width / ratio;
                 Command::none()
             }
             Message::CancelExport => {
                 self.show_modal = false;
-                self.output_height = self.height;
-                self.output_width = self.width;
                 Command::none()
             }
             Message::SaveExport => {
-                let scale = self.output_width / self.width;
-                let png_content = generate_png(
-                    self.svg_tree.as_ref().unwrap(),
-                    &Size::from_wh(self.output_width, self.output_height).unwrap(),
-                    scale,
-                );
+                // the tree holds the current layer state, zoom/pan is only applied on screen
+                let Some(document) = self.document() else {
+                    return Command::none();
+                };
+                let scale = self.output_width / document.width;
+                if self.batch != Batch::Current {
+                    // the GUI has no working directory to speak of, names are
+                    // relative to the document
+                    let dir = Path::new(&document.file_name).parent();
+                    let exported = export_batch(
+                        document,
+                        self.batch,
+                        &self.output_template,
+                        dir,
+                        scale,
+                        false,
+                    );
+                    match self.report(exported) {
+                        Some(None) => self.ask_overwrite = true,
+                        Some(Some(_)) => self.show_modal = false,
+                        None => (),
+                    }
+                    return Command::none();
+                }
+                let png_content = Size::from_wh(self.output_width, self.output_height)
+                    .ok_or_else(|| Error::Render("invalid output size".to_string()))
+                    .and_then(|size| generate_png(&document.svg_tree, &size, scale));
+                let Some(png_content) = self.report(png_content) else {
+                    return Command::none();
+                };
                 self.png_content = png_content;
-                let exported = export_png(&self.png_content, &self.output_file_name, false);
-                if exported.is_none() {
-                    self.ask_overwrite = true;
-                } else {
-                    self.show_modal = false;
-                    self.output_height = self.height;
-                    self.output_width = self.width;
+                let Some(document) = self.document() else {
+                    return Command::none();
+                };
+                let exported = export_png(&self.png_content, &document.output_file_name, false);
+                match self.report(exported) {
+                    Some(None) => self.ask_overwrite = true,
+                    Some(Some(())) => self.show_modal = false,
+                    None => (),
                 }
                 Command::none()
             }
             Message::Overwrite => {
+                let Some(document) = self.document() else {
+                    return Command::none();
+                };
+                let exported = if self.show_svg_modal {
+                    export_svg(&self.svg_output, &document.output_svg_file_name, true)
+                } else if self.batch != Batch::Current {
+                    let scale = self.output_width / document.width;
+                    let dir = Path::new(&document.file_name).parent();
+                    export_batch(
+                        document,
+                        self.batch,
+                        &self.output_template,
+                        dir,
+                        scale,
+                        true,
+                    )
+                    .map(|_| Some(()))
+                } else {
+                    export_png(&self.png_content, &document.output_file_name, true)
+                };
+                self.show_svg_modal = false;
                 self.show_modal = false;
                 self.ask_overwrite = false;
-                self.output_height = self.height;
-                self.output_width = self.width;
-                export_png(&self.png_content, &self.output_file_name, true);
+                self.report(exported);
                 Command::none()
             }
             Message::NoOverwrite => {
                 self.ask_overwrite = false;
                 Command::none()
             }
-            Message::Scroll(scroll) => {
-                self.current_scroll += 0.05 * scroll;
-                if self.current_scroll < 1.0 {
-                    self.current_scroll = 1.0;
-                    self.matrix_transform = (1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+            Message::OpenSaveSvg => {
+                self.show_svg_modal = self.document().is_some();
+                Command::none()
+            }
+            Message::OutSvgFileName(output_svg_file_name) => {
+                if let Some(document) = self.document_mut() {
+                    document.output_svg_file_name = output_svg_file_name;
+                }
+                Command::none()
+            }
+            Message::StripHidden(strip_hidden) => {
+                self.strip_hidden = strip_hidden;
+                Command::none()
+            }
+            Message::CancelSaveSvg => {
+                self.show_svg_modal = false;
+                Command::none()
+            }
+            Message::SaveSvg => {
+                let Some(document) = self.document() else {
+                    return Command::none();
+                };
+                // the file as the user wrote it, only the layers change
+                let svg_output =
+                    set_visible_layers(&document.source, &document.layers).and_then(|svg_output| {
+                        if self.strip_hidden {
+                            remove_hidden_layers(&svg_output, &document.layers)
+                        } else {
+                            Ok(svg_output)
+                        }
+                    });
+                let Some(svg_output) = self.report(svg_output) else {
+                    return Command::none();
+                };
+                self.svg_output = svg_output;
+                let Some(document) = self.document() else {
+                    return Command::none();
+                };
+                let exported = export_svg(&self.svg_output, &document.output_svg_file_name, false);
+                match self.report(exported) {
+                    Some(None) => self.ask_overwrite = true,
+                    Some(Some(())) => self.show_svg_modal = false,
+                    None => (),
+                }
+                Command::none()
+            }
+            // the layer panel scrolls by itself
+            Message::Scroll(_) if !self.over_view() => Command::none(),
+            Message::Scroll(delta) => {
+                let (cursor, modifiers) = ((self.current_x, self.current_y), self.modifiers);
+                let Some(document) = self.document_mut() else {
+                    return Command::none();
+                };
+                // touchpads report pixels, mouse wheels report lines
+                let (x, y) = match delta {
+                    ScrollDelta::Lines { x, y } => (x * LINE_HEIGHT, y * LINE_HEIGHT),
+                    ScrollDelta::Pixels { x, y } => (x, y),
+                };
+                if modifiers.command() {
+                    // scale around the point under the cursor, so that it stays in place
+                    let zoom = document.current_scroll * 1.05_f32.powf(y / LINE_HEIGHT);
+                    document.zoom_around(zoom, cursor);
                 } else {
-                    let scale = 1.0 + 0.05 * scroll;
-
-                    let (a, b, c, d, e, f) = self.matrix_transform;
-                    self.matrix_transform = (
-                        a * scale,
-                        b * scale,
-                        c * scale,
-                        d * scale,
-                        e + (1.0 - scale) * self.width / 2.0,
-                        f + (1.0 - scale) * self.height / 2.0,
-                    );
+                    // shift turns a vertical wheel into a horizontal one
+                    let (x, y) = if modifiers.shift() && x == 0.0 {
+                        (y, 0.0)
+                    } else {
+                        (x, y)
+                    };
+                    let fit = document.fit_scale(bounds);
+                    if fit > 0.0 {
+                        document.pan(x / fit, y / fit);
+                    }
                 }
-                self.svg_content = transform_svg(&self.svg_content, self.matrix_transform);
+                Command::none()
+            }
+            Message::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers;
                 Command::none()
             }
             Message::StartPan => {
-                self.panning = true;
+                self.panning = self.over_view();
+                Command::none()
+            }
+            Message::StartResize => {
+                self.resizing = true;
                 Command::none()
             }
             Message::CursorMoved(x, y) => {
-                let old_x = self.current_x;
-                let old_y = self.current_y;
-                self.current_x = (x / self.current_width as f32) * self.width;
-                self.current_y = (y / self.current_height as f32) * self.height;
-                if self.panning && self.current_scroll > 1.0 {
-                    let (a, b, c, d, e, f) = self.matrix_transform;
-                    self.matrix_transform = (
-                        a,
-                        b,
-                        c,
-                        d,
-                        e + self.current_x - old_x,
-                        f + self.current_y - old_y,
-                    );
-                    self.svg_content = transform_svg(&self.svg_content, self.matrix_transform);
+                // the panel is right of the view, growing when dragged leftwards,
+                // as long as the views keep some room, each compared document
+                // having its own panel
+                if self.resizing {
+                    let view_width = match self.compare {
+                        Some(_) if !self.show_diff => {
+                            self.view_bounds.width.min(self.compare_bounds.width)
+                        }
+                        _ => self.view_bounds.width,
+                    };
+                    let max_width = self.panel_width + view_width - MIN_VIEW_WIDTH;
+                    self.panel_width = (self.panel_width + self.cursor.x - x)
+                        .clamp(MIN_PANEL_WIDTH, max_width.max(MIN_PANEL_WIDTH));
+                }
+                self.cursor = Point::new(x, y);
+                // a drag stays on the side where it started
+                if !self.panning {
+                    self.compare_side = self.compare.is_some()
+                        && !self.show_diff
+                        && self.compare_bounds.contains(Point::new(x, y));
                 }
+                let (hovered, view_bounds) = self.hovered();
+                let Some(document_size) = self
+                    .documents
+                    .get(hovered)
+                    .and_then(|document| Size::from_wh(document.width, document.height))
+                else {
+                    return Command::none();
+                };
+                // the cursor position is in the window, the document in the widget
+                let (current_x, current_y) = to_document_units(
+                    document_size,
+                    (view_bounds.width, view_bounds.height),
+                    (x - view_bounds.x, y - view_bounds.y),
+                );
+                // both documents share the zoom/pan of the active one
+                let Some(document) = self.documents.get_mut(self.active) else {
+                    return Command::none();
+                };
+                if self.panning {
+                    document.pan(current_x - self.current_x, current_y - self.current_y);
+                }
+                self.current_x = current_x;
+                self.current_y = current_y;
                 Command::none()
             }
             Message::EndPan => {
                 self.panning = false;
+                self.resizing = false;
                 Command::none()
             }
             Message::Reset => {
-                self.matrix_transform = (1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
-                self.current_scroll = 1.0;
-                self.svg_content = transform_svg(&self.svg_content, self.matrix_transform);
+                if let Some(document) = self.document_mut() {
+                    document.reset();
+                }
+                Command::none()
+            }
+            Message::FitWidth => {
+                if let Some(document) = self.document_mut() {
+                    document.set_matrix(fit_width, bounds);
+                }
+                Command::none()
+            }
+            Message::ActualSize => {
+                if let Some(document) = self.document_mut() {
+                    document.set_matrix(actual_size, physical_bounds);
+                }
                 Command::none()
             }
-            Message::Resized(width, height) => {
-                self.current_width = width;
-                self.current_height = height;
+            Message::KeyPressed(key, modifiers) => {
+                let modal = self.show_modal || self.show_svg_modal || self.show_open_modal;
+                if modal || self.error.is_some() {
+                    return Command::none();
+                }
+                let action = self
+                    .bindings
+                    .iter()
+                    .find(|(shortcut, _)| shortcut.matches(&key, modifiers))
+                    .map(|(_, action)| *action);
+                match action {
+                    Some(action) => self.run_action(action),
+                    None => Command::none(),
+                }
+            }
+            Message::ZoomTo(percentage) => {
+                if let Some(document) = self.document_mut() {
+                    let fit = document.fit_scale(physical_bounds);
+                    if fit > 0.0 {
+                        let center = (document.width / 2.0, document.height / 2.0);
+                        document.zoom_around(percentage / 100.0 / fit, center);
+                    }
+                }
+                Command::none()
+            }
+            Message::ViewBounds(bounds) => {
+                self.view_bounds = bounds;
+                Command::none()
+            }
+            Message::CompareBounds(bounds) => {
+                self.compare_bounds = bounds;
                 Command::none()
             }
         }
     }
 
-    fn view(&self) -> Element<Self::Message> {
-        let handle = svg::Handle::from_memory(self.svg_content.clone());
+    fn view(&self) -> Element<'_, Self::Message> {
+        let open_button = button(text("Open…").size(16)).on_press(Message::OpenFile);
 
-        let inner_svg = svg(handle).width(Length::Fill).height(Length::Fill);
-        let svg = ExtendedSvg { inner: inner_svg };
-
-        let checkboxes: Vec<Checkbox<Self::Message>> = self
-            .layers
-            .iter()
-            .map(|(l, c)| {
-                checkbox(l.as_str(), *c)
-                    .on_toggle(|v| Message::ToggleLayerVisibility(l.to_string(), v))
-            })
-            .collect();
+        let Some(document) = self.document() else {
+            let message = self
+                .error
+                .as_ref()
+                .map(Error::to_string)
+                .unwrap_or_default();
+            let start = container(
+                column![text(message).size(16), open_button]
+                    .spacing(20)
+                    .align_items(iced::Alignment::Center),
+            )
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y();
+            return if self.show_open_modal {
+                Modal::new(start, Some(self.open_modal())).into()
+            } else {
+                start.into()
+            };
+        };
 
-        let mut row = Row::new()
+        let matrix_transform = document.matrix_transform;
+        let view_bounds = Message::ViewBounds;
+        let documents: Element<Message> = match self.compare {
+            Some(compared) if self.show_diff => self
+                .document_view(self.active, Some(compared), matrix_transform, view_bounds)
+                .into(),
+            Some(compared) => row![
+                self.document_view(self.active, None, matrix_transform, view_bounds),
+                self.document_view(compared, None, matrix_transform, Message::CompareBounds)
+            ]
             .spacing(20)
-            .align_items(iced::Alignment::Center)
-            .width(Length::Fill);
-
-        for checkbox in checkboxes {
-            row = row.push(checkbox)
-        }
+            .height(Length::Fill)
+            .into(),
+            None => self
+                .document_view(self.active, None, matrix_transform, view_bounds)
+                .into(),
+        };
 
         let export_button = button(text("Export to PNG").size(16)).on_press(Message::OpenExport);
-        let reset_button = button(text("Reset zoom/pan").size(16)).on_press(Message::Reset);
+        let save_svg_button = button(text("Save SVG as…").size(16)).on_press(Message::OpenSaveSvg);
+        let reset_button = button(text("Fit to window").size(16)).on_press(Message::Reset);
+        let fit_width_button = button(text("Fit width").size(16)).on_press(Message::FitWidth);
+        let actual_size_button = button(text("Actual size").size(16)).on_press(Message::ActualSize);
+        let preset_names: Vec<String> = document.presets.iter().map(|p| p.name.clone()).collect();
+        let presets = row![
+            pick_list(preset_names, None::<String>, Message::ApplyPreset)
+                .placeholder("Presets…")
+                .text_size(16),
+            text_input("Preset name", &self.preset_name)
+                .on_input(Message::PresetName)
+                .on_submit(Message::SavePreset)
+                .width(Length::Fixed(150.0))
+                .size(16),
+            button(text("Save preset").size(16)).on_press(Message::SavePreset),
+        ]
+        .spacing(10);
         let content = container(
             column![
-                svg,
-                container(row).width(Length::Fill).center_x(),
-                container(row![export_button, reset_button].spacing(50))
-                    .width(Length::Fill)
-                    .center_x()
+                self.tab_bar(),
+                documents,
+                container(
+                    row![
+                        row![open_button, export_button, save_svg_button].spacing(20),
+                        row![reset_button, fit_width_button, actual_size_button].spacing(20),
+                        presets
+                    ]
+                    .spacing(50)
+                )
+                .width(Length::Fill)
+                .center_x(),
+                self.status_bar(document)
             ]
             .spacing(20)
             .height(Length::Fill),
@@ -383,10 +1378,19 @@ impl Application for Picture {
         .center_x()
         .center_y();
 
-        if self.show_modal {
+        if self.error.is_some() {
+            let modal = self.error_modal();
+            Modal::new(content, Some(modal)).into()
+        } else if self.show_open_modal {
+            let modal = self.open_modal();
+            Modal::new(content, Some(modal)).into()
+        } else if self.show_modal || self.show_svg_modal {
             if self.ask_overwrite {
                 let modal = self.overwrite_modal();
                 Modal::new(content, Some(modal)).into()
+            } else if self.show_svg_modal {
+                let modal = self.save_svg_modal();
+                Modal::new(content, Some(modal)).into()
             } else {
                 let modal = self.save_modal();
                 Modal::new(content, Some(modal)).into()
@@ -396,3 +1400,75 @@ impl Application for Picture {
         }
     }
 }
+
+/// Whether the layer or one of its sublayers has the filter in its name, the
+/// filter being lowercase.
+fn matches_filter(layers: &[Layer], index: usize, filter: &str) -> bool {
+    layers[index].name.to_lowercase().contains(filter)
+        || (0..layers.len())
+            .any(|i| layers[i].parent == Some(index) && matches_filter(layers, i, filter))
+}
+
+fn layer_tree<'a>(
+    document: usize,
+    layers: &'a [Layer],
+    index: usize,
+    solo: bool,
+    filter: &str,
+) -> Column<'a, Message> {
+    let layer = &layers[index];
+    let children: Vec<usize> = layers
+        .iter()
+        .enumerate()
+        .filter(|(i, l)| l.parent == Some(index) && matches_filter(layers, *i, filter))
+        .map(|(i, _)| i)
+        .collect();
+
+    let indent = horizontal_space().width(Length::Fixed(20.0 * layer.depth as f32));
+    let expander: Element<Message> = if children.is_empty() {
+        horizontal_space().width(Length::Fixed(16.0)).into()
+    } else {
+        let icon = if layer.expanded {
+            Bootstrap::CaretDownFill
+        } else {
+            Bootstrap::CaretRightFill
+        };
+        button(text(char::from(icon)).font(BOOTSTRAP_FONT).size(16))
+            .on_press(Message::ToggleLayerExpanded(document, index))
+            .padding(0)
+            .style(theme::Button::Text)
+            .into()
+    };
+    let layer_checkbox = checkbox(layer.name.as_str(), layer.visible).on_toggle(move |v| {
+        if solo {
+            Message::SoloLayer(document, index)
+        } else {
+            Message::ToggleLayerVisibility(document, index, v)
+        }
+    });
+
+    let opacity = slider(0.0..=1.0, layer.opacity, move |v| {
+        Message::LayerOpacity(document, index, v)
+    })
+    .on_release(Message::ApplyLayers(document))
+    .step(0.05)
+    .width(Length::Fixed(60.0));
+
+    let mut tree = column![row![
+        indent,
+        expander,
+        layer_checkbox,
+        horizontal_space(),
+        opacity
+    ]
+    .spacing(5)
+    .align_items(iced::Alignment::Center)]
+    .spacing(5);
+    // sublayers matching the filter show even in collapsed layers
+    if layer.expanded || !filter.is_empty() {
+        for child in children {
+            tree = tree.push(layer_tree(document, layers, child, solo, filter));
+        }
+    }
+    tree
+}